@@ -0,0 +1,176 @@
+//! Talking to a running ssh-agent: listing the keys loaded into it and adding new ones.
+
+use std::env;
+use std::io::{Read as _, Write as _};
+use std::os::unix::net::UnixStream;
+use std::path::Path;
+use std::time::Duration;
+
+use anyhow::Context as _;
+
+/// Message asking the agent for its identities.
+const SSH_AGENTC_REQUEST_IDENTITIES: u8 = 11;
+/// Reply listing the agent's identities.
+const SSH_AGENT_IDENTITIES_ANSWER: u8 = 12;
+/// Message adding a private key to the agent.
+const SSH2_AGENTC_ADD_IDENTITY: u8 = 17;
+/// Reply accepting a request.
+const SSH_AGENT_SUCCESS: u8 = 6;
+/// Reply refusing a request.
+const SSH_AGENT_FAILURE: u8 = 5;
+
+/// Largest reply accepted from the agent, far more than any number of keys would need.
+const MAX_REPLY: usize = 256 * 1024;
+
+/// A key loaded into the agent. The agent only ever hands out the public half.
+#[derive(Debug)]
+pub(crate) struct Identity {
+    /// Public key in the SSH wire format, as base64-encoded in a public key line
+    pub blob: Vec<u8>,
+    /// Comment the key was added with, usually user@host or the path of the key file
+    pub comment: String,
+}
+
+impl Identity {
+    /// The key type at the start of the blob, such as `ssh-ed25519`.
+    pub fn key_type(&self) -> Result<String, anyhow::Error> {
+        let key_type = Reader(&self.blob).string()?;
+        Ok(String::from_utf8_lossy(key_type).into_owned())
+    }
+
+    /// The key as a line of a `.pub` file: `type base64 comment`.
+    pub fn public_key_line(&self) -> Result<String, anyhow::Error> {
+        let mut line = format!("{} {}", self.key_type()?, base64::encode(&self.blob));
+        if !self.comment.is_empty() {
+            line.push(' ');
+            line.push_str(&self.comment);
+        }
+        line.push('\n');
+        Ok(line)
+    }
+}
+
+/// Asks the agent listening on `SSH_AUTH_SOCK` for the keys loaded into it.
+pub(crate) fn identities() -> Result<Vec<Identity>, anyhow::Error> {
+    Agent::connect()?.identities()
+}
+
+/// A connection to the agent listening on `SSH_AUTH_SOCK`.
+#[derive(Debug)]
+pub(crate) struct Agent {
+    stream: UnixStream,
+}
+
+impl Agent {
+    /// Connects to the agent, failing with a hint on how to start one if there is none.
+    pub fn connect() -> Result<Self, anyhow::Error> {
+        let socket = env::var_os("SSH_AUTH_SOCK")
+            .filter(|socket| !socket.is_empty())
+            .context(
+                "SSH_AUTH_SOCK is not set, so there is no ssh-agent to talk to; start one with \
+                 eval \"$(ssh-agent)\"",
+            )?;
+        let socket = Path::new(&socket);
+        let stream = UnixStream::connect(socket).with_context(|| {
+            format!(
+                "Failed to connect to the ssh-agent at {} (from SSH_AUTH_SOCK)",
+                socket.display()
+            )
+        })?;
+        stream.set_read_timeout(Some(Duration::from_secs(10)))?;
+        Ok(Agent { stream })
+    }
+
+    /// Lists the keys loaded into the agent.
+    pub fn identities(&mut self) -> Result<Vec<Identity>, anyhow::Error> {
+        parse_identities(&self.request(&[SSH_AGENTC_REQUEST_IDENTITIES])?)
+    }
+
+    /// Adds a private key to the agent. `key` is the key type followed by the private key
+    /// fields, as in the private section of an OpenSSH private key file.
+    pub fn add_identity(&mut self, key: &[u8], comment: &str) -> Result<(), anyhow::Error> {
+        let mut message = vec![SSH2_AGENTC_ADD_IDENTITY];
+        message.extend_from_slice(key);
+        message.extend_from_slice(&(comment.len() as u32).to_be_bytes());
+        message.extend_from_slice(comment.as_bytes());
+        let reply = self.request(&message)?;
+        match Reader(&reply).byte()? {
+            SSH_AGENT_SUCCESS => Ok(()),
+            SSH_AGENT_FAILURE => anyhow::bail!("The ssh-agent refused to add the key"),
+            other => anyhow::bail!("Unexpected reply of type {} from the ssh-agent", other),
+        }
+    }
+
+    /// Sends a message to the agent and reads its reply.
+    fn request(&mut self, message: &[u8]) -> Result<Vec<u8>, anyhow::Error> {
+        // Each message is its length as a u32, then its type and contents.
+        let mut framed = (message.len() as u32).to_be_bytes().to_vec();
+        framed.extend_from_slice(message);
+        self.stream
+            .write_all(&framed)
+            .context("Failed to send a request to the ssh-agent")?;
+        let mut len = [0; 4];
+        self.stream
+            .read_exact(&mut len)
+            .context("Failed to read the reply of the ssh-agent")?;
+        let len = u32::from_be_bytes(len) as usize;
+        if len > MAX_REPLY {
+            anyhow::bail!(
+                "The ssh-agent sent a reply of {} bytes, too large to read",
+                len
+            );
+        }
+        let mut reply = vec![0; len];
+        self.stream
+            .read_exact(&mut reply)
+            .context("Failed to read the reply of the ssh-agent")?;
+        Ok(reply)
+    }
+}
+
+/// Parses an identities answer: a count, then the key blob and comment of each identity.
+fn parse_identities(reply: &[u8]) -> Result<Vec<Identity>, anyhow::Error> {
+    let mut reader = Reader(reply);
+    match reader.byte()? {
+        SSH_AGENT_IDENTITIES_ANSWER => {}
+        SSH_AGENT_FAILURE => anyhow::bail!("The ssh-agent refused to list its keys"),
+        other => anyhow::bail!("Unexpected reply of type {} from the ssh-agent", other),
+    }
+    let count = reader.u32()?;
+    let mut identities = Vec::new();
+    for _ in 0..count {
+        let blob = reader.string()?.to_vec();
+        let comment = String::from_utf8_lossy(reader.string()?).into_owned();
+        identities.push(Identity { blob, comment });
+    }
+    Ok(identities)
+}
+
+/// Reads the fields of a message in the SSH wire format.
+struct Reader<'a>(&'a [u8]);
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, n: usize) -> Result<&'a [u8], anyhow::Error> {
+        if self.0.len() < n {
+            anyhow::bail!("The reply of the ssh-agent was cut short");
+        }
+        let (field, rest) = self.0.split_at(n);
+        self.0 = rest;
+        Ok(field)
+    }
+
+    fn byte(&mut self) -> Result<u8, anyhow::Error> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u32(&mut self) -> Result<u32, anyhow::Error> {
+        let bytes = self.take(4)?;
+        Ok(u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+
+    /// A string is its length as a u32 followed by that many bytes.
+    fn string(&mut self) -> Result<&'a [u8], anyhow::Error> {
+        let len = self.u32()? as usize;
+        self.take(len)
+    }
+}
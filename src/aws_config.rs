@@ -0,0 +1,157 @@
+//! Settings and profile names read from the AWS shared config and credentials files, as the
+//! AWS CLI does.
+
+use std::collections::BTreeMap;
+use std::env;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use anyhow::Context as _;
+use rusoto_core::Region;
+
+/// The AWS shared config file: `$AWS_CONFIG_FILE`, or `~/.aws/config`.
+pub fn default_aws_config_path() -> Option<PathBuf> {
+    match env::var_os("AWS_CONFIG_FILE") {
+        Some(path) if !path.is_empty() => Some(PathBuf::from(path)),
+        _ => Some(
+            PathBuf::from(env::var_os("HOME")?)
+                .join(".aws")
+                .join("config"),
+        ),
+    }
+}
+
+/// The AWS shared credentials file: `$AWS_SHARED_CREDENTIALS_FILE`, or `~/.aws/credentials`.
+pub fn default_aws_credentials_path() -> Option<PathBuf> {
+    match env::var_os("AWS_SHARED_CREDENTIALS_FILE") {
+        Some(path) if !path.is_empty() => Some(PathBuf::from(path)),
+        _ => Some(
+            PathBuf::from(env::var_os("HOME")?)
+                .join(".aws")
+                .join("credentials"),
+        ),
+    }
+}
+
+/// A profile defined in the AWS shared config or credentials file.
+#[derive(Debug)]
+pub struct Profile {
+    /// Name of the profile, as given to --aws-profile
+    pub name: String,
+    /// Whether the config file has a section for it
+    pub in_config: bool,
+    /// Whether the credentials file has a section for it
+    pub in_credentials: bool,
+}
+
+/// The profiles defined in the shared config file at `config` and the shared credentials file
+/// at `credentials`, sorted by name. Missing files define no profiles.
+pub fn profile_names(
+    config: Option<&Path>,
+    credentials: Option<&Path>,
+) -> Result<Vec<Profile>, anyhow::Error> {
+    let mut profiles = BTreeMap::<String, (bool, bool)>::new();
+    if let Some(contents) = config.map(read_optional).transpose()?.flatten() {
+        for name in section_names(&contents, section_profile) {
+            profiles.entry(name).or_default().0 = true;
+        }
+    }
+    if let Some(contents) = credentials.map(read_optional).transpose()?.flatten() {
+        // The credentials file names its sections after the profiles, without a prefix.
+        for name in section_names(&contents, |header| Some(header.trim())) {
+            profiles.entry(name).or_default().1 = true;
+        }
+    }
+    Ok(profiles
+        .into_iter()
+        .map(|(name, (in_config, in_credentials))| Profile {
+            name,
+            in_config,
+            in_credentials,
+        })
+        .collect())
+}
+
+/// The contents of the file at `path`, or `None` if there is no such file.
+fn read_optional(path: &Path) -> Result<Option<String>, anyhow::Error> {
+    match fs::read_to_string(path) {
+        Ok(contents) => Ok(Some(contents)),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e).with_context(|| format!("{}", path.display())),
+    }
+}
+
+/// The profiles named by the section headers of an INI file, as read by `profile`.
+fn section_names(contents: &str, profile: fn(&str) -> Option<&str>) -> Vec<String> {
+    contents
+        .lines()
+        .map(str::trim)
+        .filter_map(|line| line.strip_prefix('[')?.strip_suffix(']'))
+        .filter_map(profile)
+        .filter(|name| !name.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+/// The region set for `profile` in the AWS shared config file at `path`, if any. A missing
+/// file sets no region.
+pub fn profile_region(path: &Path, profile: &str) -> Result<Option<Region>, anyhow::Error> {
+    let contents = match read_optional(path)? {
+        Some(contents) => contents,
+        None => return Ok(None),
+    };
+    match profile_setting(&contents, profile, "region") {
+        Some(region) => region.parse::<Region>().map(Some).with_context(|| {
+            format!(
+                "Invalid region for profile {} in {}",
+                profile,
+                path.display()
+            )
+        }),
+        None => Ok(None),
+    }
+}
+
+/// The value of `key` in the section for `profile`. Profiles other than `default` have their
+/// sections named `[profile NAME]`; `default` may be named either way. Indented lines continue
+/// the setting above them, such as the settings nested under `s3 =`, and are never top-level
+/// settings themselves. The last value given wins.
+fn profile_setting(contents: &str, profile: &str, key: &str) -> Option<String> {
+    let mut in_profile = false;
+    let mut value = None;
+    for line in contents.lines() {
+        let trimmed = line.trim();
+        if trimmed.is_empty() || trimmed.starts_with('#') || trimmed.starts_with(';') {
+            continue;
+        }
+        if let Some(header) = trimmed
+            .strip_prefix('[')
+            .and_then(|rest| rest.strip_suffix(']'))
+        {
+            in_profile = section_profile(header) == Some(profile);
+            continue;
+        }
+        if !in_profile || line.starts_with(char::is_whitespace) {
+            continue;
+        }
+        if let Some((name, setting)) = trimmed.split_once('=') {
+            if name.trim() == key {
+                value = Some(setting.trim().to_string()).filter(|v| !v.is_empty());
+            }
+        }
+    }
+    value
+}
+
+/// The profile a section header (without its brackets) belongs to.
+fn section_profile(header: &str) -> Option<&str> {
+    let header = header.trim();
+    let mut words = header.splitn(2, char::is_whitespace);
+    match (words.next(), words.next()) {
+        (Some("profile"), Some(name)) => Some(name.trim()),
+        (Some("default"), None) => Some("default"),
+        // The AWS CLI ignores other sections without the profile prefix.
+        _ => None,
+    }
+}
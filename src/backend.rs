@@ -0,0 +1,533 @@
+//! Abstraction over the service the secret is stored in.
+
+use std::str::FromStr;
+
+use anyhow::Context as _;
+use async_trait::async_trait;
+use chrono::{DateTime, SecondsFormat, Utc};
+use rusoto_core::RusotoError;
+use rusoto_secretsmanager::{
+    CreateSecretError, CreateSecretRequest, CreateSecretResponse, DescribeSecretError,
+    DescribeSecretRequest, DescribeSecretResponse, GetSecretValueError, GetSecretValueRequest,
+    GetSecretValueResponse, ListSecretVersionIdsError, ListSecretVersionIdsRequest,
+    ListSecretVersionIdsResponse, ListSecretsError, ListSecretsRequest, ListSecretsResponse,
+    PutSecretValueError, PutSecretValueRequest, PutSecretValueResponse,
+    ReplicateSecretToRegionsError, ReplicateSecretToRegionsRequest,
+    ReplicateSecretToRegionsResponse, SecretsManager, SecretsManagerClient, Tag, TagResourceError,
+    TagResourceRequest, UpdateSecretError, UpdateSecretRequest, UpdateSecretResponse,
+    UpdateSecretVersionStageRequest, UpdateSecretVersionStageResponse,
+};
+use uuid::Uuid;
+
+use crate::files::{decode, decode_format, Files, Format, Payload, SECRET_SIZE_LIMIT};
+
+/// Tag in which put records when it last updated the secret, as an RFC 3339 time, for
+/// put --only-if-newer to compare with.
+pub(crate) const UPDATED_AT_TAG: &str = "ssh-keys:updated-at";
+
+/// Which service the secret is stored in.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum BackendKind {
+    /// AWS Secrets Manager
+    SecretsManager,
+    /// A `SecureString` parameter in AWS Systems Manager Parameter Store
+    Ssm,
+    /// A JSON file on local disk
+    Local,
+}
+
+impl FromStr for BackendKind {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "secrets-manager" => Ok(BackendKind::SecretsManager),
+            "ssm" => Ok(BackendKind::Ssm),
+            "local" => Ok(BackendKind::Local),
+            _ => anyhow::bail!(
+                "Expected one of secrets-manager, ssm or local but got {:?}",
+                s
+            ),
+        }
+    }
+}
+
+/// The operations ssh-keys needs from the service holding the secret.
+///
+/// Requests and responses use the Secrets Manager types. Only reading and writing the secret
+/// value is required; the remaining operations default to returning an error, for backends
+/// that have no equivalent.
+#[async_trait]
+pub trait SecretsBackend: Send + Sync {
+    /// Reads a version of the secret value.
+    async fn get_secret_value(
+        &self,
+        request: GetSecretValueRequest,
+    ) -> Result<GetSecretValueResponse, anyhow::Error>;
+
+    /// Writes a new version of the secret value.
+    async fn put_secret_value(
+        &self,
+        request: PutSecretValueRequest,
+    ) -> Result<PutSecretValueResponse, anyhow::Error>;
+
+    /// Lists one page of the secret's versions.
+    async fn list_secret_version_ids(
+        &self,
+        _request: ListSecretVersionIdsRequest,
+    ) -> Result<ListSecretVersionIdsResponse, anyhow::Error> {
+        Err(anyhow::anyhow!(
+            "Listing secret versions is not supported by this backend"
+        ))
+    }
+
+    /// Creates the secret, along with its first version.
+    async fn create_secret(
+        &self,
+        _request: CreateSecretRequest,
+    ) -> Result<CreateSecretResponse, anyhow::Error> {
+        Err(anyhow::anyhow!(
+            "Creating the secret is not supported by this backend"
+        ))
+    }
+
+    /// Updates the secret's metadata.
+    async fn update_secret(
+        &self,
+        _request: UpdateSecretRequest,
+    ) -> Result<UpdateSecretResponse, anyhow::Error> {
+        Err(anyhow::anyhow!(
+            "Updating the secret is not supported by this backend"
+        ))
+    }
+
+    /// Attaches tags to the secret.
+    async fn tag_resource(&self, _request: TagResourceRequest) -> Result<(), anyhow::Error> {
+        Err(anyhow::anyhow!(
+            "Tagging the secret is not supported by this backend"
+        ))
+    }
+
+    /// Moves a staging label to or from a version of the secret.
+    async fn update_secret_version_stage(
+        &self,
+        _request: UpdateSecretVersionStageRequest,
+    ) -> Result<UpdateSecretVersionStageResponse, anyhow::Error> {
+        Err(anyhow::anyhow!(
+            "Changing staging labels is not supported by this backend"
+        ))
+    }
+
+    /// Reads the secret's metadata, including where it is replicated to.
+    async fn describe_secret(
+        &self,
+        _request: DescribeSecretRequest,
+    ) -> Result<DescribeSecretResponse, anyhow::Error> {
+        Err(anyhow::anyhow!(
+            "Describing the secret is not supported by this backend"
+        ))
+    }
+
+    /// Starts replicating the secret to other regions.
+    async fn replicate_secret_to_regions(
+        &self,
+        _request: ReplicateSecretToRegionsRequest,
+    ) -> Result<ReplicateSecretToRegionsResponse, anyhow::Error> {
+        Err(anyhow::anyhow!(
+            "Replicating the secret is not supported by this backend"
+        ))
+    }
+
+    /// Lists one page of the secrets in the account and region.
+    async fn list_secrets(
+        &self,
+        _request: ListSecretsRequest,
+    ) -> Result<ListSecretsResponse, anyhow::Error> {
+        Err(anyhow::anyhow!(
+            "Listing secrets is not supported by this backend"
+        ))
+    }
+
+    /// Largest serialized secret value in bytes that the backend accepts.
+    fn size_limit(&self) -> usize {
+        SECRET_SIZE_LIMIT
+    }
+}
+
+#[async_trait]
+impl<B: SecretsBackend + ?Sized> SecretsBackend for Box<B> {
+    async fn get_secret_value(
+        &self,
+        request: GetSecretValueRequest,
+    ) -> Result<GetSecretValueResponse, anyhow::Error> {
+        (**self).get_secret_value(request).await
+    }
+
+    async fn put_secret_value(
+        &self,
+        request: PutSecretValueRequest,
+    ) -> Result<PutSecretValueResponse, anyhow::Error> {
+        (**self).put_secret_value(request).await
+    }
+
+    async fn list_secret_version_ids(
+        &self,
+        request: ListSecretVersionIdsRequest,
+    ) -> Result<ListSecretVersionIdsResponse, anyhow::Error> {
+        (**self).list_secret_version_ids(request).await
+    }
+
+    async fn create_secret(
+        &self,
+        request: CreateSecretRequest,
+    ) -> Result<CreateSecretResponse, anyhow::Error> {
+        (**self).create_secret(request).await
+    }
+
+    async fn update_secret(
+        &self,
+        request: UpdateSecretRequest,
+    ) -> Result<UpdateSecretResponse, anyhow::Error> {
+        (**self).update_secret(request).await
+    }
+
+    async fn tag_resource(&self, request: TagResourceRequest) -> Result<(), anyhow::Error> {
+        (**self).tag_resource(request).await
+    }
+
+    async fn update_secret_version_stage(
+        &self,
+        request: UpdateSecretVersionStageRequest,
+    ) -> Result<UpdateSecretVersionStageResponse, anyhow::Error> {
+        (**self).update_secret_version_stage(request).await
+    }
+
+    async fn describe_secret(
+        &self,
+        request: DescribeSecretRequest,
+    ) -> Result<DescribeSecretResponse, anyhow::Error> {
+        (**self).describe_secret(request).await
+    }
+
+    async fn replicate_secret_to_regions(
+        &self,
+        request: ReplicateSecretToRegionsRequest,
+    ) -> Result<ReplicateSecretToRegionsResponse, anyhow::Error> {
+        (**self).replicate_secret_to_regions(request).await
+    }
+
+    async fn list_secrets(
+        &self,
+        request: ListSecretsRequest,
+    ) -> Result<ListSecretsResponse, anyhow::Error> {
+        (**self).list_secrets(request).await
+    }
+
+    fn size_limit(&self) -> usize {
+        (**self).size_limit()
+    }
+}
+
+#[async_trait]
+impl SecretsBackend for SecretsManagerClient {
+    async fn get_secret_value(
+        &self,
+        request: GetSecretValueRequest,
+    ) -> Result<GetSecretValueResponse, anyhow::Error> {
+        log::debug!("GetSecretValue {}", request.secret_id);
+        Ok(SecretsManager::get_secret_value(self, request).await?)
+    }
+
+    async fn put_secret_value(
+        &self,
+        request: PutSecretValueRequest,
+    ) -> Result<PutSecretValueResponse, anyhow::Error> {
+        log::debug!("PutSecretValue {}", request.secret_id);
+        Ok(SecretsManager::put_secret_value(self, request).await?)
+    }
+
+    async fn list_secret_version_ids(
+        &self,
+        request: ListSecretVersionIdsRequest,
+    ) -> Result<ListSecretVersionIdsResponse, anyhow::Error> {
+        log::debug!("ListSecretVersionIds {}", request.secret_id);
+        Ok(SecretsManager::list_secret_version_ids(self, request).await?)
+    }
+
+    async fn create_secret(
+        &self,
+        request: CreateSecretRequest,
+    ) -> Result<CreateSecretResponse, anyhow::Error> {
+        log::debug!("CreateSecret {}", request.name);
+        Ok(SecretsManager::create_secret(self, request).await?)
+    }
+
+    async fn update_secret(
+        &self,
+        request: UpdateSecretRequest,
+    ) -> Result<UpdateSecretResponse, anyhow::Error> {
+        log::debug!("UpdateSecret {}", request.secret_id);
+        Ok(SecretsManager::update_secret(self, request).await?)
+    }
+
+    async fn tag_resource(&self, request: TagResourceRequest) -> Result<(), anyhow::Error> {
+        log::debug!("TagResource {}", request.secret_id);
+        Ok(SecretsManager::tag_resource(self, request).await?)
+    }
+
+    async fn update_secret_version_stage(
+        &self,
+        request: UpdateSecretVersionStageRequest,
+    ) -> Result<UpdateSecretVersionStageResponse, anyhow::Error> {
+        log::debug!("UpdateSecretVersionStage {}", request.secret_id);
+        Ok(SecretsManager::update_secret_version_stage(self, request).await?)
+    }
+
+    async fn describe_secret(
+        &self,
+        request: DescribeSecretRequest,
+    ) -> Result<DescribeSecretResponse, anyhow::Error> {
+        log::debug!("DescribeSecret {}", request.secret_id);
+        Ok(SecretsManager::describe_secret(self, request).await?)
+    }
+
+    async fn replicate_secret_to_regions(
+        &self,
+        request: ReplicateSecretToRegionsRequest,
+    ) -> Result<ReplicateSecretToRegionsResponse, anyhow::Error> {
+        log::debug!("ReplicateSecretToRegions {}", request.secret_id);
+        Ok(SecretsManager::replicate_secret_to_regions(self, request).await?)
+    }
+
+    async fn list_secrets(
+        &self,
+        request: ListSecretsRequest,
+    ) -> Result<ListSecretsResponse, anyhow::Error> {
+        log::debug!("ListSecrets");
+        Ok(SecretsManager::list_secrets(self, request).await?)
+    }
+}
+
+/// Whether `e` is the error returned by a Secrets Manager call because the secret doesn't
+/// exist.
+pub(crate) fn is_not_found(e: &anyhow::Error) -> bool {
+    matches!(
+        e.downcast_ref::<RusotoError<GetSecretValueError>>(),
+        Some(RusotoError::Service(GetSecretValueError::ResourceNotFound(
+            _
+        )))
+    ) || matches!(
+        e.downcast_ref::<RusotoError<PutSecretValueError>>(),
+        Some(RusotoError::Service(PutSecretValueError::ResourceNotFound(
+            _
+        )))
+    ) || matches!(
+        e.downcast_ref::<RusotoError<ListSecretVersionIdsError>>(),
+        Some(RusotoError::Service(
+            ListSecretVersionIdsError::ResourceNotFound(_)
+        ))
+    ) || matches!(
+        e.downcast_ref::<RusotoError<UpdateSecretError>>(),
+        Some(RusotoError::Service(UpdateSecretError::ResourceNotFound(_)))
+    ) || matches!(
+        e.downcast_ref::<RusotoError<TagResourceError>>(),
+        Some(RusotoError::Service(TagResourceError::ResourceNotFound(_)))
+    ) || matches!(
+        e.downcast_ref::<RusotoError<DescribeSecretError>>(),
+        Some(RusotoError::Service(DescribeSecretError::ResourceNotFound(
+            _
+        )))
+    ) || matches!(
+        e.downcast_ref::<RusotoError<ReplicateSecretToRegionsError>>(),
+        Some(RusotoError::Service(
+            ReplicateSecretToRegionsError::ResourceNotFound(_)
+        ))
+    )
+}
+
+/// Whether `e` is the error returned by a Secrets Manager call because the credentials aren't
+/// allowed to make it.
+pub(crate) fn is_access_denied(e: &anyhow::Error) -> bool {
+    // Access denied isn't one of the modelled errors, so it shows up as an unknown response.
+    fn check<E: std::error::Error + Send + Sync + 'static>(e: &anyhow::Error) -> bool {
+        match e.downcast_ref::<RusotoError<E>>() {
+            Some(RusotoError::Unknown(response)) => {
+                response.body_as_str().contains("AccessDeniedException")
+            }
+            _ => false,
+        }
+    }
+    check::<CreateSecretError>(e)
+        || check::<DescribeSecretError>(e)
+        || check::<GetSecretValueError>(e)
+        || check::<ListSecretVersionIdsError>(e)
+        || check::<ListSecretsError>(e)
+        || check::<PutSecretValueError>(e)
+        || check::<ReplicateSecretToRegionsError>(e)
+        || check::<TagResourceError>(e)
+        || check::<UpdateSecretError>(e)
+}
+
+/// Whether a new version put with these staging labels becomes current, as it does without any.
+pub(crate) fn is_current_stage(version_stages: Option<&[String]>) -> bool {
+    version_stages
+        .unwrap_or_default()
+        .iter()
+        .all(|stage| stage == "AWSCURRENT")
+}
+
+/// Downloads the current version of the secret and parses it into a map of file names to
+/// file entries.
+pub(crate) async fn fetch(
+    backend: &dyn SecretsBackend,
+    secret_id: String,
+) -> Result<Files, anyhow::Error> {
+    fetch_version(backend, secret_id, None, None).await
+}
+
+/// Downloads the current version of the secret like [`fetch`], along with the format it is
+/// stored in, so that changes to it can be written back without e.g. decompressing it.
+pub(crate) async fn fetch_formatted(
+    backend: &dyn SecretsBackend,
+    secret_id: String,
+) -> Result<(Files, Format), anyhow::Error> {
+    let request = GetSecretValueRequest {
+        secret_id,
+        ..Default::default()
+    };
+    let response = backend.get_secret_value(request).await?;
+    decode_format(response.secret_string, response.secret_binary.as_deref())
+}
+
+/// Reads when put last updated the secret from its [`UPDATED_AT_TAG`] tag, in seconds since
+/// the Unix epoch, or `None` if no put has recorded it yet.
+pub(crate) async fn fetch_updated_at(
+    backend: &dyn SecretsBackend,
+    secret_id: String,
+) -> Result<Option<i64>, anyhow::Error> {
+    let response = backend
+        .describe_secret(DescribeSecretRequest { secret_id })
+        .await?;
+    let value = match response
+        .tags
+        .unwrap_or_default()
+        .into_iter()
+        .find(|tag| tag.key.as_deref() == Some(UPDATED_AT_TAG))
+        .and_then(|tag| tag.value)
+    {
+        Some(value) => value,
+        None => return Ok(None),
+    };
+    let updated_at = DateTime::parse_from_rfc3339(&value)
+        .with_context(|| format!("Tag {} is not a valid time: {:?}", UPDATED_AT_TAG, value))?;
+    Ok(Some(updated_at.timestamp()))
+}
+
+/// Records the current time as when put last updated the secret, in its [`UPDATED_AT_TAG`]
+/// tag.
+pub(crate) async fn store_updated_at(
+    backend: &dyn SecretsBackend,
+    secret_id: String,
+) -> Result<(), anyhow::Error> {
+    let now = Utc::now().to_rfc3339_opts(SecondsFormat::Secs, true);
+    let request = TagResourceRequest {
+        secret_id,
+        tags: vec![Tag {
+            key: Some(UPDATED_AT_TAG.to_string()),
+            value: Some(now),
+        }],
+    };
+    backend.tag_resource(request).await
+}
+
+/// Downloads the secret version selected by id or staging label (the current version if
+/// neither is given) and parses it into a map of file names to file entries.
+pub(crate) async fn fetch_version(
+    backend: &dyn SecretsBackend,
+    secret_id: String,
+    version_id: Option<String>,
+    version_stage: Option<String>,
+) -> Result<Files, anyhow::Error> {
+    let request = GetSecretValueRequest {
+        secret_id,
+        version_id,
+        version_stage,
+    };
+    let response = backend.get_secret_value(request).await?;
+    decode(response.secret_string, response.secret_binary.as_deref())
+}
+
+/// Uploads an already serialized map of file names to file entries as a new secret version,
+/// returning the id of that version.
+pub(crate) async fn store(
+    backend: &dyn SecretsBackend,
+    secret_id: String,
+    payload: Payload,
+) -> Result<Option<String>, anyhow::Error> {
+    store_with_token(backend, secret_id, payload, None, Vec::new()).await
+}
+
+/// Like [`store`], but identifies the request with `token` (a new UUID if `None`), so that
+/// repeating it with the same token and payload doesn't create another version. With
+/// `version_stages`, the new version gets those labels instead of becoming current.
+pub(crate) async fn store_with_token(
+    backend: &dyn SecretsBackend,
+    secret_id: String,
+    payload: Payload,
+    token: Option<String>,
+    version_stages: Vec<String>,
+) -> Result<Option<String>, anyhow::Error> {
+    let (secret_string, secret_binary) = payload.into_parts();
+    let request = PutSecretValueRequest {
+        client_request_token: Some(token.unwrap_or_else(|| Uuid::new_v4().to_string())),
+        secret_binary: secret_binary.map(Into::into),
+        secret_id,
+        secret_string,
+        version_stages: if version_stages.is_empty() {
+            None
+        } else {
+            Some(version_stages.clone())
+        },
+    };
+    let response = backend.put_secret_value(request).await?;
+    if let Some(version) = &response.version_id {
+        log::info!("Secret version: {}", version);
+    }
+    if !version_stages.is_empty() {
+        log::info!("Staged the new version as {}", version_stages.join(", "));
+    }
+    Ok(response.version_id)
+}
+
+/// Creates the secret with an already serialized map of file names to file entries as its
+/// first version, returning the id of that version. The version is identified by `token`,
+/// or a new UUID if `None`.
+pub(crate) async fn create(
+    backend: &dyn SecretsBackend,
+    secret_id: String,
+    payload: Payload,
+    token: Option<String>,
+    kms_key_id: Option<String>,
+    description: Option<String>,
+    tags: Vec<Tag>,
+) -> Result<Option<String>, anyhow::Error> {
+    let (secret_string, secret_binary) = payload.into_parts();
+    let request = CreateSecretRequest {
+        client_request_token: Some(token.unwrap_or_else(|| Uuid::new_v4().to_string())),
+        description,
+        kms_key_id,
+        name: secret_id,
+        secret_binary: secret_binary.map(Into::into),
+        secret_string,
+        tags: if tags.is_empty() { None } else { Some(tags) },
+        ..Default::default()
+    };
+    let response = backend.create_secret(request).await?;
+    if let Some(arn) = response.arn {
+        log::info!("Created secret: {}", arn);
+    }
+    if let Some(version) = &response.version_id {
+        log::info!("Secret version: {}", version);
+    }
+    Ok(response.version_id)
+}
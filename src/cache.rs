@@ -0,0 +1,264 @@
+//! Local caching of the current secret value.
+
+use std::env;
+use std::fs;
+use std::io::{self, Write as _};
+use std::os::unix::fs::{DirBuilderExt as _, OpenOptionsExt as _};
+use std::path::PathBuf;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use rusoto_secretsmanager::{
+    CreateSecretRequest, CreateSecretResponse, DescribeSecretRequest, DescribeSecretResponse,
+    GetSecretValueRequest, GetSecretValueResponse, ListSecretVersionIdsRequest,
+    ListSecretVersionIdsResponse, ListSecretsRequest, ListSecretsResponse, PutSecretValueRequest,
+    PutSecretValueResponse, ReplicateSecretToRegionsRequest, ReplicateSecretToRegionsResponse,
+    TagResourceRequest, UpdateSecretRequest, UpdateSecretResponse, UpdateSecretVersionStageRequest,
+    UpdateSecretVersionStageResponse,
+};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest as _, Sha256};
+use uuid::Uuid;
+
+use crate::backend::SecretsBackend;
+
+/// The directory caches are kept in by default: `$XDG_CACHE_HOME/ssh-keys`, or
+/// `~/.cache/ssh-keys`.
+pub fn default_cache_dir() -> Option<PathBuf> {
+    let base = match env::var_os("XDG_CACHE_HOME") {
+        Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ => PathBuf::from(env::var_os("HOME")?).join(".cache"),
+    };
+    Some(base.join("ssh-keys"))
+}
+
+/// A backend that remembers the current value of each secret in a local file, and answers
+/// requests for it from that file for a while instead of asking the inner backend.
+///
+/// Every request that changes a secret removes its cached value, whether or not reads are
+/// cached, so that a later cached read can't return what it replaced. The cache files hold
+/// the keys themselves, so they are only readable by the owner.
+#[derive(Debug)]
+pub struct Cache<B> {
+    inner: B,
+    dir: PathBuf,
+    scope: String,
+    ttl: Option<Duration>,
+}
+
+/// What is kept in a cache file.
+#[derive(Deserialize, Serialize)]
+struct CachedValue {
+    arn: Option<String>,
+    name: Option<String>,
+    secret_string: Option<String>,
+    secret_binary: Option<String>,
+    version_id: Option<String>,
+    version_stages: Option<Vec<String>>,
+}
+
+impl<B: SecretsBackend> Cache<B> {
+    /// Wraps `inner`, caching secrets in `dir`. `scope` tells apart secrets with the same id
+    /// in different places, such as regions. Reads are only answered from the cache if it is
+    /// younger than `ttl`; with `None`, the cache is only invalidated.
+    pub fn new(inner: B, dir: PathBuf, scope: String, ttl: Option<Duration>) -> Self {
+        Cache {
+            inner,
+            dir,
+            scope,
+            ttl,
+        }
+    }
+
+    /// The file caching the secret `secret_id`.
+    fn path(&self, secret_id: &str) -> PathBuf {
+        let digest = Sha256::digest(format!("{}\0{}", self.scope, secret_id).as_bytes());
+        self.dir.join(format!("{:x}.json", digest))
+    }
+
+    /// Reads the cached value of `secret_id`, if there is one younger than the TTL.
+    fn load(&self, secret_id: &str, ttl: Duration) -> Option<GetSecretValueResponse> {
+        let path = self.path(secret_id);
+        let age = fs::metadata(&path).ok()?.modified().ok()?.elapsed().ok()?;
+        if age > ttl {
+            log::debug!(
+                "Cache of {} expired {}s ago",
+                secret_id,
+                (age - ttl).as_secs()
+            );
+            return None;
+        }
+        let cached = serde_json::from_slice::<CachedValue>(&fs::read(&path).ok()?).ok()?;
+        let secret_binary = match cached.secret_binary {
+            Some(binary) => Some(base64::decode(binary).ok()?.into()),
+            None => None,
+        };
+        log::debug!(
+            "Using cached value of {} from {}",
+            secret_id,
+            path.display()
+        );
+        Some(GetSecretValueResponse {
+            arn: cached.arn,
+            name: cached.name,
+            secret_binary,
+            secret_string: cached.secret_string,
+            version_id: cached.version_id,
+            version_stages: cached.version_stages,
+            ..Default::default()
+        })
+    }
+
+    /// Replaces the cached value of `secret_id`.
+    fn save(&self, secret_id: &str, response: &GetSecretValueResponse) -> Result<(), io::Error> {
+        let cached = CachedValue {
+            arn: response.arn.clone(),
+            name: response.name.clone(),
+            secret_string: response.secret_string.clone(),
+            secret_binary: response.secret_binary.as_ref().map(base64::encode),
+            version_id: response.version_id.clone(),
+            version_stages: response.version_stages.clone(),
+        };
+        fs::DirBuilder::new()
+            .recursive(true)
+            .mode(0o700)
+            .create(&self.dir)?;
+        // Write a temporary file and rename it, so that a concurrent read never sees half of
+        // the value.
+        let path = self.path(secret_id);
+        let tmp = self.dir.join(format!(".{}", Uuid::new_v4()));
+        let result = fs::OpenOptions::new()
+            .create_new(true)
+            .write(true)
+            .mode(0o600)
+            .open(&tmp)
+            .and_then(|mut f| f.write_all(&serde_json::to_vec(&cached)?))
+            .and_then(|()| fs::rename(&tmp, &path));
+        if result.is_err() {
+            let _ = fs::remove_file(&tmp);
+        }
+        result
+    }
+
+    /// Removes the cached value of `secret_id`, after a request that may have changed it.
+    fn invalidate(&self, secret_id: &str) {
+        let path = self.path(secret_id);
+        match fs::remove_file(&path) {
+            Ok(()) => log::debug!("Removed cached value of {}", secret_id),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+            Err(e) => log::warn!(
+                "Warning: failed to remove cached value of {} at {}: {}",
+                secret_id,
+                path.display(),
+                e
+            ),
+        }
+    }
+}
+
+/// Whether a request is for the current version of the secret, the only one cached.
+fn is_current(request: &GetSecretValueRequest) -> bool {
+    request.version_id.is_none()
+        && request
+            .version_stage
+            .as_deref()
+            .is_none_or(|stage| stage == "AWSCURRENT")
+}
+
+#[async_trait]
+impl<B: SecretsBackend> SecretsBackend for Cache<B> {
+    async fn get_secret_value(
+        &self,
+        request: GetSecretValueRequest,
+    ) -> Result<GetSecretValueResponse, anyhow::Error> {
+        let ttl = match self.ttl {
+            Some(ttl) if is_current(&request) => ttl,
+            _ => return self.inner.get_secret_value(request).await,
+        };
+        let secret_id = request.secret_id.clone();
+        if let Some(response) = self.load(&secret_id, ttl) {
+            return Ok(response);
+        }
+        let response = self.inner.get_secret_value(request).await?;
+        if let Err(e) = self.save(&secret_id, &response) {
+            log::warn!("Warning: failed to cache value of {}: {}", secret_id, e);
+        }
+        Ok(response)
+    }
+
+    async fn put_secret_value(
+        &self,
+        request: PutSecretValueRequest,
+    ) -> Result<PutSecretValueResponse, anyhow::Error> {
+        let secret_id = request.secret_id.clone();
+        let result = self.inner.put_secret_value(request).await;
+        self.invalidate(&secret_id);
+        result
+    }
+
+    async fn list_secret_version_ids(
+        &self,
+        request: ListSecretVersionIdsRequest,
+    ) -> Result<ListSecretVersionIdsResponse, anyhow::Error> {
+        self.inner.list_secret_version_ids(request).await
+    }
+
+    async fn create_secret(
+        &self,
+        request: CreateSecretRequest,
+    ) -> Result<CreateSecretResponse, anyhow::Error> {
+        let secret_id = request.name.clone();
+        let result = self.inner.create_secret(request).await;
+        self.invalidate(&secret_id);
+        result
+    }
+
+    async fn update_secret(
+        &self,
+        request: UpdateSecretRequest,
+    ) -> Result<UpdateSecretResponse, anyhow::Error> {
+        let secret_id = request.secret_id.clone();
+        let result = self.inner.update_secret(request).await;
+        self.invalidate(&secret_id);
+        result
+    }
+
+    async fn tag_resource(&self, request: TagResourceRequest) -> Result<(), anyhow::Error> {
+        self.inner.tag_resource(request).await
+    }
+
+    async fn update_secret_version_stage(
+        &self,
+        request: UpdateSecretVersionStageRequest,
+    ) -> Result<UpdateSecretVersionStageResponse, anyhow::Error> {
+        let secret_id = request.secret_id.clone();
+        let result = self.inner.update_secret_version_stage(request).await;
+        self.invalidate(&secret_id);
+        result
+    }
+
+    async fn describe_secret(
+        &self,
+        request: DescribeSecretRequest,
+    ) -> Result<DescribeSecretResponse, anyhow::Error> {
+        self.inner.describe_secret(request).await
+    }
+
+    async fn replicate_secret_to_regions(
+        &self,
+        request: ReplicateSecretToRegionsRequest,
+    ) -> Result<ReplicateSecretToRegionsResponse, anyhow::Error> {
+        self.inner.replicate_secret_to_regions(request).await
+    }
+
+    async fn list_secrets(
+        &self,
+        request: ListSecretsRequest,
+    ) -> Result<ListSecretsResponse, anyhow::Error> {
+        self.inner.list_secrets(request).await
+    }
+
+    fn size_limit(&self) -> usize {
+        self.inner.size_limit()
+    }
+}
@@ -0,0 +1,139 @@
+//! Implementations of the ssh-keys subcommands.
+
+use std::io::{self, IsTerminal as _, Write};
+use std::process::exit;
+
+use crate::files::Files;
+
+mod backup;
+mod cat;
+mod copy;
+mod delete;
+mod diff;
+mod doctor;
+mod edit;
+mod export_env;
+mod fingerprint;
+mod get;
+mod history;
+mod list;
+mod load;
+mod migrate;
+mod purge_versions;
+mod put;
+mod put_from_agent;
+mod put_one;
+mod rename;
+mod replicas;
+mod rotate;
+mod stats;
+mod sync;
+mod verify;
+mod versions;
+
+pub use self::backup::{backup, restore};
+pub use self::cat::cat;
+pub use self::copy::copy;
+pub use self::delete::delete;
+pub use self::diff::diff;
+pub use self::doctor::{doctor, report_check};
+pub use self::edit::edit;
+pub use self::export_env::export_env;
+pub use self::fingerprint::fingerprint;
+pub use self::get::{get, GetOpt};
+pub use self::history::history;
+pub use self::list::{list, list_matching, list_secret_ids};
+pub use self::load::load;
+pub use self::migrate::migrate;
+pub use self::purge_versions::purge_versions;
+pub use self::put::{put, PutOpt};
+pub use self::put_from_agent::put_from_agent;
+pub use self::put_one::put_one;
+pub use self::rename::rename;
+pub use self::replicas::replicas;
+pub use self::rotate::rotate;
+pub use self::stats::stats;
+pub use self::sync::sync;
+pub use self::verify::verify;
+pub use self::versions::{list_versions, versions};
+
+/// Builds the error reported when a key is not present in the stored secret.
+pub(crate) fn missing_key(name: &str, files: &Files) -> anyhow::Error {
+    let mut keys = files.keys().map(String::as_str).collect::<Vec<_>>();
+    keys.sort_unstable();
+    anyhow::anyhow!(
+        "Key {} not found in secret; available keys: {}",
+        name,
+        keys.join(", ")
+    )
+}
+
+/// Shows a change about to be made to the secret on `out` and asks to confirm it, returning
+/// whether to go ahead. `summary` completes "Are you sure you want to", and `details` has a
+/// line for each key affected, if any.
+///
+/// With `dry_run` the change is shown without asking, followed by a note that nothing was
+/// changed, and false is returned so that the caller stops before writing anything. With
+/// `yes` (and without `dry_run`) nothing is shown.
+pub(crate) fn confirm_change(
+    out: &mut dyn Write,
+    summary: &str,
+    details: &[String],
+    yes: bool,
+    dry_run: bool,
+) -> Result<bool, anyhow::Error> {
+    if yes && !dry_run {
+        return Ok(true);
+    }
+    match (dry_run, details.is_empty()) {
+        (true, true) => writeln!(out, "Would {}.", summary)?,
+        (true, false) => writeln!(out, "Would {}:", summary)?,
+        (false, true) => writeln!(out, "Are you sure you want to {}?", summary)?,
+        (false, false) => writeln!(out, "Are you sure you want to {}:", summary)?,
+    }
+    for line in details {
+        writeln!(out, "{}", line)?;
+    }
+    if dry_run {
+        writeln!(out, "Dry run: no changes were made.")?;
+        return Ok(false);
+    }
+    out.flush()?;
+    confirm()?;
+    Ok(true)
+}
+
+/// Asks the user to type yes or no, exiting the process if they answer no.
+pub(crate) fn confirm() -> Result<(), anyhow::Error> {
+    if !ask()? {
+        println!("Cancelling and exiting.");
+        exit(0);
+    }
+    Ok(())
+}
+
+/// Asks the user to type yes or no, returning whether they answered yes.
+pub(crate) fn ask() -> Result<bool, anyhow::Error> {
+    if !io::stdin().is_terminal() {
+        anyhow::bail!("Cannot ask for confirmation because stdin is not a terminal; pass --yes");
+    }
+    let mut answer = String::new();
+    loop {
+        let stdout = io::stdout();
+        let mut stdout = stdout.lock();
+        stdout.write_all("yes/no: ".as_bytes())?;
+        stdout.flush()?;
+        drop(stdout);
+        if io::stdin().read_line(&mut answer)? == 0 {
+            // Without this, end of input would leave the answer empty and ask again forever.
+            anyhow::bail!(
+                "Reached end of input without an answer; pass --yes to skip confirmation"
+            );
+        }
+        match answer.trim() {
+            "yes" | "y" | "Yes" | "YES" => return Ok(true),
+            "no" | "n" | "No" | "NO" => return Ok(false),
+            _ => answer.clear(),
+        }
+    }
+}
@@ -0,0 +1,104 @@
+use std::fs;
+use std::io::{self, Write as _};
+use std::os::unix::fs::OpenOptionsExt as _;
+use std::path::Path;
+
+use anyhow::Context as _;
+use serde::{Deserialize, Serialize};
+
+use crate::backend::{fetch, fetch_formatted, store, SecretsBackend};
+use crate::commands::confirm_change;
+use crate::files::{encode, Files, Format};
+
+/// Contents of a backup file: the stored keys along with where and when they came from.
+#[derive(Debug, Deserialize, Serialize)]
+struct Backup {
+    /// ID of the secret the keys were downloaded from
+    secret_id: String,
+    /// When the backup was taken, in RFC 3339 format
+    created: String,
+    /// The stored keys
+    files: Files,
+}
+
+/// Downloads the whole secret into a single backup file.
+pub async fn backup(
+    backend: &dyn SecretsBackend,
+    secret_id: String,
+    out: &Path,
+) -> Result<(), anyhow::Error> {
+    let files = fetch(backend, secret_id.clone()).await?;
+    let backup = Backup {
+        secret_id,
+        created: chrono::Utc::now().to_rfc3339(),
+        files,
+    };
+    // The backup holds private keys, so it gets the same protection as a key written by get.
+    let f = fs::OpenOptions::new()
+        .create_new(true)
+        .write(true)
+        .mode(0o600)
+        .open(out)
+        .with_context(|| format!("{}", out.display()))?;
+    let mut writer = io::BufWriter::new(f);
+    serde_json::to_writer_pretty(&mut writer, &backup)?;
+    writer.write_all(b"\n")?;
+    writer.flush()?;
+    log::info!(
+        "Backed up {} key(s) to {}",
+        backup.files.len(),
+        out.display()
+    );
+    Ok(())
+}
+
+/// Replaces the stored keys with the contents of a backup file.
+pub async fn restore(
+    backend: &dyn SecretsBackend,
+    secret_id: String,
+    file: &Path,
+    yes: bool,
+    dry_run: bool,
+) -> Result<(), anyhow::Error> {
+    let contents = fs::read(file).with_context(|| format!("{}", file.display()))?;
+    let backup: Backup = serde_json::from_slice(&contents)
+        .with_context(|| format!("{} is not an ssh-keys backup file", file.display()))?;
+    if backup.secret_id != secret_id {
+        log::warn!(
+            "Warning: {} is a backup of {}, not {}",
+            file.display(),
+            backup.secret_id,
+            secret_id
+        );
+    }
+    // The restored keys are stored the way the secret is now, e.g. still compressed. A secret
+    // that can't be read, which may be why it is being restored, gets the default format.
+    let format = fetch_formatted(backend, secret_id.clone())
+        .await
+        .map_or_else(|_| Format::default(), |(_, format)| format);
+    let summary = format!(
+        "restore {} from the backup taken at {}, deleting its existing contents",
+        secret_id, backup.created
+    );
+    let mut keys = backup.files.keys().collect::<Vec<_>>();
+    keys.sort();
+    let details = keys
+        .iter()
+        .map(|k| format!("  - {}", k))
+        .collect::<Vec<_>>();
+    if !confirm_change(&mut io::stdout(), &summary, &details, yes, dry_run)? {
+        return Ok(());
+    }
+    store(
+        backend,
+        secret_id,
+        encode(
+            &backup.files,
+            format.storage,
+            format.compact,
+            backend.size_limit(),
+        )?,
+    )
+    .await?;
+    Ok(())
+}
@@ -0,0 +1,41 @@
+use std::io::{self, Write as _};
+
+use anyhow::Context as _;
+
+use crate::backend::{fetch, SecretsBackend};
+use crate::commands::missing_key;
+use crate::crypt::{decrypt_entry, read_passphrase};
+
+/// Writes the contents of a single stored key to stdout, decrypting it with `passphrase` if
+/// it was put with one.
+pub async fn cat(
+    backend: &dyn SecretsBackend,
+    secret_id: String,
+    name: String,
+    passphrase: Option<Option<String>>,
+) -> Result<(), anyhow::Error> {
+    let passphrase = passphrase
+        .map(|value| read_passphrase(value, false))
+        .transpose()?;
+    let files = fetch(backend, secret_id).await?;
+    let mut entry = files
+        .get(&name)
+        .ok_or_else(|| missing_key(&name, &files))?
+        .clone();
+    if entry.encrypted {
+        let passphrase = passphrase.with_context(|| {
+            format!("Key {} is encrypted; pass --passphrase to decrypt it", name)
+        })?;
+        decrypt_entry(&mut entry, &passphrase).with_context(|| {
+            format!(
+                "Key {} could not be decrypted; is the passphrase right?",
+                name
+            )
+        })?;
+    }
+    let stdout = io::stdout();
+    let mut stdout = stdout.lock();
+    stdout.write_all(&entry.contents)?;
+    stdout.flush()?;
+    Ok(())
+}
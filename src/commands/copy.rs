@@ -0,0 +1,41 @@
+use std::io;
+
+use crate::backend::{fetch, fetch_formatted, store, SecretsBackend};
+use crate::commands::{confirm_change, missing_key};
+use crate::files::encode;
+
+/// Copies a single stored key into another secret, e.g. to promote it from a staging secret
+/// to a production one.
+pub async fn copy(
+    backend: &dyn SecretsBackend,
+    secret_id: String,
+    name: String,
+    dest_secret_id: String,
+    yes: bool,
+    dry_run: bool,
+) -> Result<(), anyhow::Error> {
+    if dest_secret_id == secret_id {
+        anyhow::bail!("Cannot copy {} from {} into itself", name, secret_id);
+    }
+    let source = fetch(backend, secret_id.clone()).await?;
+    let entry = match source.get(&name) {
+        Some(entry) => entry.clone(),
+        None => return Err(missing_key(&name, &source)),
+    };
+    let (mut files, format) = fetch_formatted(backend, dest_secret_id.clone()).await?;
+    let mut summary = format!("copy {} from {} to {}", name, secret_id, dest_secret_id);
+    if files.contains_key(&name) {
+        summary.push_str(&format!(", overwriting the existing {}", name));
+    }
+    if !confirm_change(&mut io::stdout(), &summary, &[], yes, dry_run)? {
+        return Ok(());
+    }
+    files.insert(name, entry);
+    store(
+        backend,
+        dest_secret_id,
+        encode(&files, format.storage, format.compact, backend.size_limit())?,
+    )
+    .await?;
+    Ok(())
+}
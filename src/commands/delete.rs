@@ -0,0 +1,30 @@
+use std::io;
+
+use crate::backend::{fetch_formatted, store, SecretsBackend};
+use crate::commands::{confirm_change, missing_key};
+use crate::files::encode;
+
+/// Removes a single key from the stored secret.
+pub async fn delete(
+    backend: &dyn SecretsBackend,
+    secret_id: String,
+    name: String,
+    yes: bool,
+    dry_run: bool,
+) -> Result<(), anyhow::Error> {
+    let (mut files, format) = fetch_formatted(backend, secret_id.clone()).await?;
+    if files.remove(&name).is_none() {
+        return Err(missing_key(&name, &files));
+    }
+    let summary = format!("delete {} from ssh-keys", name);
+    if !confirm_change(&mut io::stdout(), &summary, &[], yes, dry_run)? {
+        return Ok(());
+    }
+    store(
+        backend,
+        secret_id,
+        encode(&files, format.storage, format.compact, backend.size_limit())?,
+    )
+    .await?;
+    Ok(())
+}
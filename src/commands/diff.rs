@@ -0,0 +1,144 @@
+use std::io::{self, Write as _};
+use std::path::Path;
+
+use owo_colors::{OwoColorize as _, Stream};
+use similar::TextDiff;
+
+use crate::backend::{fetch, SecretsBackend};
+use crate::crypt::{decrypt_files, read_passphrase};
+use crate::files::{Entry, Files};
+use crate::scan::{scan, ScanOpt};
+
+/// How a key differs between a local directory and the stored secret.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) enum Change {
+    /// Only in the local directory
+    Added,
+    /// Only in the stored secret
+    Removed,
+    /// In both, with different contents or mode
+    Modified,
+    /// In both and identical
+    Unchanged,
+}
+
+/// Classifies every key in either `local` or `stored`, sorted by name.
+pub(crate) fn changes(local: &Files, stored: &Files) -> Vec<(String, Change)> {
+    let mut keys = local.keys().chain(stored.keys()).collect::<Vec<_>>();
+    keys.sort();
+    keys.dedup();
+    keys.into_iter()
+        .map(|k| {
+            let change = match (stored.get(k), local.get(k)) {
+                (None, _) => Change::Added,
+                (_, None) => Change::Removed,
+                (Some(old), Some(new))
+                    if old.contents != new.contents || old.mode(k) != new.mode(k) =>
+                {
+                    Change::Modified
+                }
+                _ => Change::Unchanged,
+            };
+            (k.clone(), change)
+        })
+        .collect()
+}
+
+/// Compares the files in a local directory to the stored keys, printing the status of each
+/// key and a unified diff of modified ones. Returns whether there were any differences. Keys
+/// put with a passphrase are decrypted with `passphrase` first.
+pub async fn diff(
+    backend: &dyn SecretsBackend,
+    secret_id: String,
+    indir: &Path,
+    scan_opt: &ScanOpt,
+    passphrase: Option<Option<String>>,
+) -> Result<bool, anyhow::Error> {
+    let local = scan(indir, scan_opt)?;
+    let passphrase = passphrase
+        .map(|value| read_passphrase(value, false))
+        .transpose()?;
+    let mut stored = fetch(backend, secret_id).await?;
+    decrypt_files(&mut stored, passphrase.as_ref())?;
+    let stdout = io::stdout();
+    let mut stdout = stdout.lock();
+    let mut changed = false;
+    for (k, change) in changes(&local, &stored) {
+        match change {
+            Change::Added => {
+                let line = format!("+ {} (added)", k);
+                writeln!(
+                    stdout,
+                    "{}",
+                    line.if_supports_color(Stream::Stdout, |t| t.green())
+                )?
+            }
+            Change::Removed => {
+                let line = format!("- {} (removed)", k);
+                writeln!(
+                    stdout,
+                    "{}",
+                    line.if_supports_color(Stream::Stdout, |t| t.red())
+                )?
+            }
+            Change::Modified => {
+                let (old, new) = (&stored[&k], &local[&k]);
+                let line = format!("~ {} (modified)", k);
+                writeln!(
+                    stdout,
+                    "{}",
+                    line.if_supports_color(Stream::Stdout, |t| t.yellow())
+                )?;
+                if old.contents != new.contents {
+                    for line in unified_diff(&k, old, new).lines() {
+                        writeln!(stdout, "{}", colored_diff_line(line))?;
+                    }
+                }
+                if old.mode(&k) != new.mode(&k) {
+                    stdout.write_all(
+                        format!("mode {:o} -> {:o}\n", old.mode(&k), new.mode(&k)).as_bytes(),
+                    )?;
+                }
+            }
+            Change::Unchanged => {
+                stdout.write_all(format!("  {} (unchanged)\n", k).as_bytes())?;
+                continue;
+            }
+        }
+        changed = true;
+    }
+    Ok(changed)
+}
+
+/// Colors a line of a unified diff by whether it was added, removed or starts a hunk.
+fn colored_diff_line(line: &str) -> String {
+    if line.starts_with("+++") || line.starts_with("---") {
+        line.if_supports_color(Stream::Stdout, |t| t.bold())
+            .to_string()
+    } else if line.starts_with('+') {
+        line.if_supports_color(Stream::Stdout, |t| t.green())
+            .to_string()
+    } else if line.starts_with('-') {
+        line.if_supports_color(Stream::Stdout, |t| t.red())
+            .to_string()
+    } else if line.starts_with("@@") {
+        line.if_supports_color(Stream::Stdout, |t| t.cyan())
+            .to_string()
+    } else {
+        line.to_string()
+    }
+}
+
+/// Renders a unified diff from the stored to the local contents of a key.
+fn unified_diff(name: &str, old: &Entry, new: &Entry) -> String {
+    match (
+        std::str::from_utf8(&old.contents),
+        std::str::from_utf8(&new.contents),
+    ) {
+        (Ok(old), Ok(new)) => TextDiff::from_lines(old, new)
+            .unified_diff()
+            .header(&format!("stored/{}", name), &format!("local/{}", name))
+            .to_string(),
+        _ => "Binary contents differ\n".to_string(),
+    }
+}
@@ -0,0 +1,57 @@
+use owo_colors::{OwoColorize as _, Stream};
+use rusoto_secretsmanager::GetSecretValueRequest;
+
+use crate::backend::SecretsBackend;
+use crate::files::decode;
+
+/// Prints a pass or fail line for the check `name`, with what it found or why it failed, and
+/// returns whether it passed.
+pub fn report_check(name: &str, result: Result<String, anyhow::Error>) -> bool {
+    match result {
+        Ok(found) => {
+            println!(
+                "{}  {}: {}",
+                "PASS".if_supports_color(Stream::Stdout, |t| t.green()),
+                name,
+                found
+            );
+            true
+        }
+        Err(e) => {
+            // Rusoto errors give the message of the error they wrap as their own, so it would
+            // otherwise be repeated.
+            let mut causes = e.chain().map(ToString::to_string).collect::<Vec<_>>();
+            causes.dedup();
+            println!(
+                "{}  {}: {}",
+                "FAIL".if_supports_color(Stream::Stdout, |t| t.red()),
+                name,
+                causes.join(": ")
+            );
+            false
+        }
+    }
+}
+
+/// Checks that the secret exists and can be read, and that it holds keys stored by ssh-keys,
+/// printing a line for each check. Returns whether they all passed; nothing is changed.
+pub async fn doctor(backend: &dyn SecretsBackend, secret_id: String) -> bool {
+    let request = GetSecretValueRequest {
+        secret_id: secret_id.clone(),
+        ..Default::default()
+    };
+    let response = match backend.get_secret_value(request).await {
+        Ok(response) => response,
+        Err(e) => return report_check("Secret readable", Err(e)),
+    };
+    let version = response.version_id.as_deref().unwrap_or("-");
+    report_check(
+        "Secret readable",
+        Ok(format!("{} (version {})", secret_id, version)),
+    );
+    let files = decode(response.secret_string, response.secret_binary.as_deref());
+    report_check(
+        "Secret format",
+        files.map(|files| format!("{} key(s) stored", files.len())),
+    )
+}
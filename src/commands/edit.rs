@@ -0,0 +1,129 @@
+use std::collections::BTreeMap;
+use std::env;
+use std::fs;
+use std::io::{self, Write as _};
+use std::os::unix::fs::OpenOptionsExt as _;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use anyhow::Context as _;
+use uuid::Uuid;
+
+use crate::backend::{fetch_formatted, store, SecretsBackend};
+use crate::commands::diff::{changes, Change};
+use crate::commands::{ask, confirm_change};
+use crate::files::{encode, Files};
+
+/// Opens the stored secret as JSON in the user's editor and uploads the result.
+pub async fn edit(
+    backend: &dyn SecretsBackend,
+    secret_id: String,
+    yes: bool,
+    dry_run: bool,
+) -> Result<(), anyhow::Error> {
+    let (stored, format) = fetch_formatted(backend, secret_id.clone()).await?;
+    // Sorted, so the editor shows the keys in the same order every time.
+    let original = serde_json::to_string_pretty(&stored.iter().collect::<BTreeMap<_, _>>())?;
+    let path = env::temp_dir().join(format!("ssh-keys-{}.json", Uuid::new_v4()));
+    let edited = {
+        // The file holds every private key, so it is removed as soon as editing is over
+        // (however that happens) and before anything can exit the process.
+        let temp = TempFile::create(path, &original)?;
+        edit_until_valid(&temp.0)?
+    };
+    if edited == stored {
+        log::info!("0 changes, nothing to do");
+        return Ok(());
+    }
+    let details = changes(&edited, &stored)
+        .into_iter()
+        .filter_map(|(k, change)| {
+            let action = match change {
+                Change::Added => "add",
+                Change::Removed => "delete",
+                Change::Modified => "update",
+                Change::Unchanged => return None,
+            };
+            Some(format!("  - {} ({})", k, action))
+        })
+        .collect::<Vec<_>>();
+    let summary = "make the following changes to ssh-keys";
+    if !confirm_change(&mut io::stdout(), summary, &details, yes, dry_run)? {
+        return Ok(());
+    }
+    store(
+        backend,
+        secret_id,
+        encode(
+            &edited,
+            format.storage,
+            format.compact,
+            backend.size_limit(),
+        )?,
+    )
+    .await?;
+    Ok(())
+}
+
+/// Runs the editor on `path` until it holds a valid secret, or the user gives up.
+fn edit_until_valid(path: &Path) -> Result<Files, anyhow::Error> {
+    loop {
+        run_editor(path)?;
+        let contents = fs::read_to_string(path).with_context(|| format!("{}", path.display()))?;
+        match serde_json::from_str(&contents) {
+            Ok(files) => return Ok(files),
+            Err(e) => {
+                log::warn!("Warning: the edited secret is not valid: {}", e);
+                println!("Re-open the editor to fix it?");
+                if !ask()? {
+                    anyhow::bail!("Not uploading the invalid secret");
+                }
+            }
+        }
+    }
+}
+
+/// Opens `path` in `$EDITOR`, `$VISUAL` or vi and waits for it to exit.
+fn run_editor(path: &Path) -> Result<(), anyhow::Error> {
+    let editor = env::var("EDITOR")
+        .or_else(|_| env::var("VISUAL"))
+        .unwrap_or_else(|_| "vi".to_string());
+    // The editor is run through the shell, since it often includes arguments (e.g.
+    // "code --wait").
+    let status = Command::new("sh")
+        .arg("-c")
+        .arg(format!("{} \"$1\"", editor))
+        .arg("sh")
+        .arg(path)
+        .status()
+        .with_context(|| format!("Failed to run editor {:?}", editor))?;
+    if !status.success() {
+        anyhow::bail!("Editor {:?} exited with {}; not uploading", editor, status);
+    }
+    Ok(())
+}
+
+/// A file only the owner can read, removed when dropped.
+struct TempFile(PathBuf);
+
+impl TempFile {
+    /// Creates the file at `path` holding `contents`.
+    fn create(path: PathBuf, contents: &str) -> Result<Self, anyhow::Error> {
+        let mut f = fs::OpenOptions::new()
+            .create_new(true)
+            .write(true)
+            .mode(0o600)
+            .open(&path)
+            .with_context(|| format!("{}", path.display()))?;
+        let temp = TempFile(path);
+        f.write_all(contents.as_bytes())
+            .with_context(|| format!("{}", temp.0.display()))?;
+        Ok(temp)
+    }
+}
+
+impl Drop for TempFile {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.0);
+    }
+}
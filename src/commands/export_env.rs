@@ -0,0 +1,64 @@
+use std::collections::HashMap;
+use std::io::{self, Write as _};
+
+use crate::backend::{fetch, SecretsBackend};
+use crate::crypt::{decrypt_files, read_passphrase};
+
+/// Prints a shell `export` statement for each stored key, setting `SSH_KEY_<NAME>` to its
+/// base64-encoded contents.
+///
+/// `<NAME>` is the key name with ASCII letters uppercased and every character other than an
+/// ASCII letter or digit replaced by `_`, so `id_ed25519.pub` becomes
+/// `SSH_KEY_ID_ED25519_PUB`. Keys whose names map to the same variable are refused rather
+/// than one silently replacing the other. Keys put with a passphrase are exported decrypted
+/// with `passphrase`.
+pub async fn export_env(
+    backend: &dyn SecretsBackend,
+    secret_id: String,
+    passphrase: Option<Option<String>>,
+) -> Result<(), anyhow::Error> {
+    let passphrase = passphrase
+        .map(|value| read_passphrase(value, false))
+        .transpose()?;
+    let mut files = fetch(backend, secret_id).await?;
+    decrypt_files(&mut files, passphrase.as_ref())?;
+    let mut keys = files.keys().collect::<Vec<_>>();
+    keys.sort();
+    let mut variables = HashMap::new();
+    for k in &keys {
+        if let Some(other) = variables.insert(variable_name(k), k) {
+            anyhow::bail!(
+                "Keys {} and {} would both be exported as {}",
+                other,
+                k,
+                variable_name(k)
+            );
+        }
+    }
+    let stdout = io::stdout();
+    let mut stdout = stdout.lock();
+    for k in keys {
+        writeln!(
+            stdout,
+            "export {}=\"{}\"",
+            variable_name(k),
+            base64::encode(&files[k].contents)
+        )?;
+    }
+    Ok(())
+}
+
+/// The environment variable a key is exported as.
+fn variable_name(name: &str) -> String {
+    let sanitized = name
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() {
+                c.to_ascii_uppercase()
+            } else {
+                '_'
+            }
+        })
+        .collect::<String>();
+    format!("SSH_KEY_{}", sanitized)
+}
@@ -0,0 +1,39 @@
+use std::io::{self, Write as _};
+
+use crate::backend::{fetch, SecretsBackend};
+use crate::crypt::{decrypt_files, read_passphrase};
+use crate::files::is_public_key_name;
+use crate::keys::parse_public_key;
+
+/// Prints the SHA256 fingerprint of each stored public key, decrypting those put with a
+/// passphrase with `passphrase`.
+pub async fn fingerprint(
+    backend: &dyn SecretsBackend,
+    secret_id: String,
+    passphrase: Option<Option<String>>,
+) -> Result<(), anyhow::Error> {
+    let passphrase = passphrase
+        .map(|value| read_passphrase(value, false))
+        .transpose()?;
+    let mut files = fetch(backend, secret_id).await?;
+    // Only the public keys are needed, so only they have to be decrypted.
+    files.retain(|k, _| is_public_key_name(k));
+    decrypt_files(&mut files, passphrase.as_ref())?;
+    let mut keys = files.keys().collect::<Vec<_>>();
+    keys.sort();
+    let stdout = io::stdout();
+    let mut stdout = stdout.lock();
+    for k in keys {
+        if !is_public_key_name(k) {
+            continue;
+        }
+        let parsed = std::str::from_utf8(&files[k].contents)
+            .map_err(anyhow::Error::from)
+            .and_then(parse_public_key);
+        match parsed {
+            Ok(key) => stdout.write_all(format!("{}  {}\n", key.fingerprint(), k).as_bytes())?,
+            Err(e) => log::warn!("Skipping {}: {}", k, e),
+        }
+    }
+    Ok(())
+}
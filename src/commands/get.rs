@@ -0,0 +1,815 @@
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::io::{self, IsTerminal as _, Write};
+use std::os::unix::fs::{DirBuilderExt as _, OpenOptionsExt as _, PermissionsExt as _};
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use anyhow::Context as _;
+use dialoguer::MultiSelect;
+use filetime::FileTime;
+use glob::Pattern;
+use serde_json::json;
+use sha2::{Digest as _, Sha256};
+use structopt::StructOpt;
+use tokio::sync::Semaphore;
+use tokio::task;
+use uuid::Uuid;
+
+use crate::backend::{fetch_version, SecretsBackend};
+use crate::crypt::{decrypt_files, read_passphrase};
+use crate::exit::Failure;
+use crate::files::{is_public_key_name, is_retired_key_name, validate_key_path, Files};
+use crate::keys::restore_comments;
+use crate::output::OutputFormat;
+
+/// Name of the one key whose lines are merged with the local file by --merge-known-hosts.
+const KNOWN_HOSTS: &str = "known_hosts";
+
+/// Name of the manifest written by --checksums.
+const CHECKSUMS: &str = "SHA256SUMS";
+
+/// Options for the get command
+#[derive(Debug, StructOpt)]
+pub struct GetOpt {
+    /// An empty output directory
+    #[structopt(required_unless_one = &["to-tar", "authorized-keys"])]
+    pub outdir: Option<PathBuf>,
+
+    /// Write the keys into this tar archive (- for stdout) instead of outdir
+    #[structopt(long, conflicts_with = "outdir")]
+    pub to_tar: Option<PathBuf>,
+
+    /// Instead of writing each key, write the lines of every stored public key (*.pub) but
+    /// those replaced by rotate (*.old.pub), without duplicates, into this authorized_keys
+    /// file with mode 600
+    #[structopt(long, conflicts_with_all = &["outdir", "to-tar"])]
+    pub authorized_keys: Option<PathBuf>,
+
+    /// Keep the lines already in the --authorized-keys file and only add the missing ones,
+    /// so that running the same get again changes nothing
+    #[structopt(long, requires = "authorized-keys")]
+    pub merge: bool,
+
+    /// Allow a non-empty outdir, overwriting files whose names collide with stored keys
+    #[structopt(long)]
+    pub force: bool,
+
+    /// Merge a stored key named known_hosts into an existing outdir/known_hosts, keeping the
+    /// local lines and adding stored ones it lacks, instead of refusing or replacing it; only
+    /// applies to the key named exactly known_hosts
+    #[structopt(long, conflicts_with = "prefix")]
+    pub merge_known_hosts: bool,
+
+    /// Treat outdir as an ssh directory such as ~/.ssh: create it with mode 700, and warn if
+    /// it already exists with looser permissions
+    #[structopt(long)]
+    pub ssh_dir: bool,
+
+    /// Also write a SHA256SUMS manifest of the keys into outdir, for checking them later with
+    /// `sha256sum -c SHA256SUMS`
+    #[structopt(long, conflicts_with = "to-tar")]
+    pub checksums: bool,
+
+    /// Set the modification time of each file to the one it had when it was put, where the
+    /// secret records it
+    #[structopt(long)]
+    pub preserve_mtime: bool,
+
+    /// Fetch the secret version with this id instead of the current one
+    #[structopt(long, conflicts_with = "version-stage")]
+    pub version_id: Option<String>,
+
+    /// Fetch the secret version with this staging label (e.g. AWSPREVIOUS)
+    #[structopt(long)]
+    pub version_stage: Option<String>,
+
+    /// Only write keys whose names match this glob, e.g. 'id_*' (may be repeated)
+    #[structopt(long, number_of_values = 1)]
+    pub only: Vec<Pattern>,
+
+    /// Choose the keys to write from a list of the stored ones (or of those matching --only);
+    /// needs a terminal
+    #[structopt(long)]
+    pub select: bool,
+
+    /// Write every public key (*.pub) with this octal mode, e.g. 644, instead of the mode it
+    /// was put with (or 444 if unknown)
+    #[structopt(long, parse(try_from_str = parse_mode))]
+    pub pub_mode: Option<u32>,
+
+    /// Write every other file with this octal mode, e.g. 600, instead of the mode it was put
+    /// with (or 400 if unknown)
+    #[structopt(long, parse(try_from_str = parse_mode))]
+    pub private_mode: Option<u32>,
+
+    /// Prepend this to the name of every file written, e.g. 'work-' to write id_ed25519 as
+    /// work-id_ed25519; modes are still chosen from the original names
+    #[structopt(long)]
+    pub prefix: Option<String>,
+
+    /// Put back the comments removed from public keys by put --strip-comments
+    #[structopt(long)]
+    pub restore_comments: bool,
+
+    /// Decrypt the keys encrypted by put --passphrase with this passphrase; asked for on the
+    /// terminal if given without a value
+    #[structopt(long, require_equals = true)]
+    pub passphrase: Option<Option<String>>,
+
+    /// Maximum number of files to write at the same time
+    #[structopt(long, default_value = "8")]
+    pub concurrency: usize,
+}
+
+/// Writes the stored keys into a directory or tar archive.
+pub async fn get(
+    backend: &dyn SecretsBackend,
+    secret_id: String,
+    opt: GetOpt,
+    output: OutputFormat,
+) -> Result<(), anyhow::Error> {
+    let GetOpt {
+        outdir,
+        to_tar,
+        authorized_keys,
+        merge,
+        force,
+        merge_known_hosts,
+        ssh_dir,
+        checksums,
+        preserve_mtime,
+        version_id,
+        version_stage,
+        only,
+        select,
+        prefix,
+        pub_mode,
+        private_mode,
+        restore_comments,
+        passphrase,
+        concurrency,
+    } = opt;
+    if concurrency == 0 {
+        anyhow::bail!("--concurrency must be at least 1");
+    }
+    let passphrase = passphrase
+        .map(|value| read_passphrase(value, false))
+        .transpose()?;
+    if let Some(path) = authorized_keys {
+        let mut files = fetch_selected(
+            backend,
+            secret_id,
+            version_id,
+            version_stage,
+            &only,
+            select,
+            prefix.as_deref(),
+        )
+        .await?;
+        decrypt_files(&mut files, passphrase.as_ref())?;
+        if restore_comments {
+            restore_key_comments(&mut files)?;
+        }
+        return write_authorized_keys(&path, &files, merge, force, output);
+    }
+    if let Some(archive) = to_tar {
+        if archive == Path::new("-") && output == OutputFormat::Json {
+            anyhow::bail!("Cannot write both a tar archive and JSON output to stdout");
+        }
+        let mut files = fetch_selected(
+            backend,
+            secret_id,
+            version_id,
+            version_stage,
+            &only,
+            select,
+            prefix.as_deref(),
+        )
+        .await?;
+        decrypt_files(&mut files, passphrase.as_ref())?;
+        override_modes(&mut files, pub_mode, private_mode);
+        if restore_comments {
+            restore_key_comments(&mut files)?;
+        }
+        let written = write_tar(&archive, files)?;
+        return print_written(output, &archive, &written);
+    }
+    let outdir = match outdir {
+        Some(outdir) => outdir,
+        None => anyhow::bail!("Either outdir or --to-tar is required"),
+    };
+    let created = if outdir.exists() {
+        let metadata = outdir
+            .metadata()
+            .with_context(|| format!("{}", outdir.display()))?;
+        if !metadata.is_dir() {
+            return Err(Failure::Conflict.error(format!(
+                "Provided outdir {} is not an empty directory",
+                outdir.display()
+            )));
+        }
+        let mut existing = fs::read_dir(&outdir)?.collect::<Result<Vec<_>, _>>()?;
+        if merge_known_hosts {
+            existing.retain(|entry| entry.file_name() != KNOWN_HOSTS);
+        }
+        if !force && !existing.is_empty() {
+            return Err(Failure::Conflict.error(format!(
+                "Provided outdir {} is not an empty directory",
+                outdir.display()
+            )));
+        }
+        if ssh_dir && metadata.permissions().mode() & 0o077 != 0 {
+            log::warn!(
+                "Warning: {} has mode {:o}; ssh may refuse keys in it unless it is 700",
+                outdir.display(),
+                metadata.permissions().mode() & 0o7777
+            );
+        }
+        false
+    } else {
+        fs::create_dir_all(&outdir).with_context(|| format!("{}", outdir.display()))?;
+        if ssh_dir {
+            fs::set_permissions(&outdir, fs::Permissions::from_mode(0o700))
+                .with_context(|| format!("{}", outdir.display()))?;
+        }
+        true
+    };
+    let result = async {
+        let mut files = fetch_selected(
+            backend,
+            secret_id,
+            version_id,
+            version_stage,
+            &only,
+            select,
+            prefix.as_deref(),
+        )
+        .await?;
+        decrypt_files(&mut files, passphrase.as_ref())?;
+        override_modes(&mut files, pub_mode, private_mode);
+        if restore_comments {
+            restore_key_comments(&mut files)?;
+        }
+        if merge_known_hosts {
+            merge_local_known_hosts(&outdir, &mut files)?;
+        }
+        if !preserve_mtime {
+            for v in files.values_mut() {
+                v.mtime = None;
+            }
+        }
+        let manifest = if checksums {
+            Some(checksum_manifest(&files)?)
+        } else {
+            None
+        };
+        let written = write_dir(&outdir, files, concurrency).await?;
+        // The manifest goes last, so that it only exists once every file it lists does.
+        if let Some(manifest) = manifest {
+            let path = outdir.join(CHECKSUMS);
+            if !force && path.exists() {
+                anyhow::bail!("{} already exists", path.display());
+            }
+            write_replacing(&path, 0o644, manifest.as_bytes())?;
+            log::debug!("Wrote {} (mode 644)", path.display());
+        }
+        Ok::<_, anyhow::Error>(written)
+    }
+    .await;
+    let written = match result {
+        Ok(written) => written,
+        Err(e) => {
+            if created {
+                let _ = fs::remove_dir(&outdir);
+            }
+            return Err(e);
+        }
+    };
+    print_written(output, &outdir, &written)
+}
+
+/// Adds the lines of an existing outdir/known_hosts to the stored one, so that writing it
+/// keeps every host already known locally.
+fn merge_local_known_hosts(outdir: &Path, files: &mut Files) -> Result<(), anyhow::Error> {
+    let entry = match files.get_mut(KNOWN_HOSTS) {
+        Some(entry) => entry,
+        None => return Ok(()),
+    };
+    let path = outdir.join(KNOWN_HOSTS);
+    let local = match fs::read(&path) {
+        Ok(local) => local,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(()),
+        Err(e) => return Err(e).with_context(|| format!("{}", path.display())),
+    };
+    let mut merged = local.split(|b| *b == b'\n').collect::<Vec<_>>();
+    if merged.last() == Some(&&[][..]) {
+        merged.pop();
+    }
+    let mut seen = merged.iter().copied().collect::<HashSet<_>>();
+    let stored = entry.contents.clone();
+    for line in stored.split(|b| *b == b'\n') {
+        if !line.is_empty() && seen.insert(line) {
+            merged.push(line);
+        }
+    }
+    let mut contents = merged.join(&b'\n');
+    contents.push(b'\n');
+    log::debug!(
+        "Merged stored known_hosts into {} ({} lines)",
+        path.display(),
+        merged.len()
+    );
+    entry.contents = contents;
+    Ok(())
+}
+
+/// Writes the lines of every public key in `files` into the authorized_keys file at `path`,
+/// skipping duplicates, blank lines and comments. Keys replaced by rotate are left out, so
+/// that they no longer give access. With `merge` the lines already in the file
+/// come first and are kept as they are, and the file is left alone if no line is missing.
+fn write_authorized_keys(
+    path: &Path,
+    files: &Files,
+    merge: bool,
+    force: bool,
+    output: OutputFormat,
+) -> Result<(), anyhow::Error> {
+    let existing = match fs::read_to_string(path) {
+        Ok(existing) => Some(existing),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => None,
+        Err(e) => return Err(e).with_context(|| format!("{}", path.display())),
+    };
+    if existing.is_some() && !merge && !force {
+        return Err(Failure::Conflict.error(format!(
+            "{} already exists; pass --merge to add to it or --force to replace it",
+            path.display()
+        )));
+    }
+    let mut lines = Vec::new();
+    let mut seen = HashSet::new();
+    if let (true, Some(existing)) = (merge, &existing) {
+        for line in existing.lines() {
+            seen.insert(line.trim());
+            lines.push(line);
+        }
+    }
+    let kept = lines.len();
+    let mut keys = files
+        .keys()
+        .filter(|k| is_public_key_name(k) && !is_retired_key_name(k))
+        .collect::<Vec<_>>();
+    if keys.is_empty() {
+        anyhow::bail!("No public keys (*.pub) are stored to write into authorized_keys");
+    }
+    keys.sort();
+    for k in keys {
+        let contents = std::str::from_utf8(&files[k].contents)
+            .with_context(|| format!("Public key {} is not valid utf-8", k))?;
+        for line in contents.lines().map(str::trim) {
+            if !line.is_empty() && !line.starts_with('#') && seen.insert(line) {
+                lines.push(line);
+            }
+        }
+    }
+    let added = lines.len() - kept;
+    if merge && existing.is_some() && added == 0 {
+        log::debug!("{} already has every stored public key", path.display());
+    } else {
+        let mut contents = lines.join("\n");
+        contents.push('\n');
+        create_parent(path)?;
+        // Renamed into place, so that sshd never reads half of it.
+        write_replacing(path, 0o600, contents.as_bytes())?;
+        log::debug!("Wrote {} (mode 600)", path.display());
+    }
+    if output == OutputFormat::Json {
+        let value = json!({
+            "destination": path,
+            "count": lines.len(),
+            "added": added,
+        });
+        println!("{}", value);
+    } else {
+        log::info!(
+            "{} holds {} key(s), {} of them new",
+            path.display(),
+            lines.len(),
+            added
+        );
+    }
+    Ok(())
+}
+
+/// Builds a manifest of the SHA256 of each key in the format read by `sha256sum -c`.
+fn checksum_manifest(files: &Files) -> Result<String, anyhow::Error> {
+    if files.contains_key(CHECKSUMS) {
+        anyhow::bail!(
+            "Cannot write {} because a stored key has that name",
+            CHECKSUMS
+        );
+    }
+    let mut keys = files.keys().collect::<Vec<_>>();
+    keys.sort();
+    let mut manifest = String::new();
+    for k in keys {
+        let digest = Sha256::digest(&files[k].contents);
+        manifest.push_str(&format!("{:x}  {}\n", digest, k));
+    }
+    Ok(manifest)
+}
+
+/// Reports how many files were written to `dest` and their total size, along with the name
+/// and size of each one in JSON output.
+fn print_written(
+    output: OutputFormat,
+    dest: &Path,
+    written: &[(String, usize)],
+) -> Result<(), anyhow::Error> {
+    let bytes = written.iter().map(|(_, size)| size).sum::<usize>();
+    if output == OutputFormat::Json {
+        let files = written
+            .iter()
+            .map(|(k, size)| json!({ "name": k, "size": size }))
+            .collect::<Vec<_>>();
+        let value = json!({
+            "destination": dest,
+            "count": written.len(),
+            "bytes": bytes,
+            "files": files,
+        });
+        println!("{}", value);
+    } else if dest != Path::new("-") {
+        // A tar archive written to stdout can't be followed by text on stdout.
+        log::info!(
+            "Wrote {} file(s), {} bytes, to {}",
+            written.len(),
+            bytes,
+            dest.display()
+        );
+    }
+    Ok(())
+}
+
+/// Replaces the mode of every public key with `pub_mode` and of every other file with
+/// `private_mode`, where given.
+fn override_modes(files: &mut Files, pub_mode: Option<u32>, private_mode: Option<u32>) {
+    for (k, v) in files.iter_mut() {
+        let mode = if is_public_key_name(k) {
+            pub_mode
+        } else {
+            private_mode
+        };
+        if mode.is_some() {
+            v.mode = mode;
+        }
+    }
+}
+
+/// Appends the comments stored with each public key put with --strip-comments back onto its
+/// lines.
+fn restore_key_comments(files: &mut Files) -> Result<(), anyhow::Error> {
+    for (k, v) in files.iter_mut() {
+        let comments = match v.comments.take() {
+            Some(comments) => comments,
+            None => continue,
+        };
+        let contents = std::str::from_utf8(&v.contents)
+            .with_context(|| format!("Key {} has comments but is not valid utf-8", k))?;
+        v.contents = restore_comments(contents, &comments).into_bytes();
+    }
+    Ok(())
+}
+
+/// Parses an octal Unix file mode given on the command line, such as 600 or 0644.
+fn parse_mode(s: &str) -> Result<u32, anyhow::Error> {
+    match u32::from_str_radix(s, 8) {
+        Ok(mode) if mode <= 0o7777 => Ok(mode),
+        _ => anyhow::bail!("Expected an octal file mode such as 600 but got {:?}", s),
+    }
+}
+
+/// Fetches the selected version of the secret, keeping only the keys matching `only` if any
+/// patterns are given, and renaming them with `prefix` if one is given.
+async fn fetch_selected(
+    backend: &dyn SecretsBackend,
+    secret_id: String,
+    version_id: Option<String>,
+    version_stage: Option<String>,
+    only: &[Pattern],
+    select: bool,
+    prefix: Option<&str>,
+) -> Result<Files, anyhow::Error> {
+    if select && !io::stdin().is_terminal() {
+        anyhow::bail!("--select needs a terminal; choose the keys with --only instead");
+    }
+    let mut files = fetch_version(backend, secret_id, version_id, version_stage).await?;
+    if !only.is_empty() {
+        files.retain(|k, _| only.iter().any(|p| p.matches(k)));
+        if files.is_empty() {
+            anyhow::bail!("No stored keys match the patterns given with --only");
+        }
+    }
+    if select {
+        let mut keys = files.keys().cloned().collect::<Vec<_>>();
+        keys.sort();
+        let chosen = MultiSelect::new()
+            .with_prompt("Keys to write (space to toggle, enter to confirm)")
+            .items(&keys)
+            .interact()
+            .context("Failed to ask which keys to write")?;
+        if chosen.is_empty() {
+            anyhow::bail!("No keys selected");
+        }
+        let chosen = chosen.into_iter().map(|i| &keys[i]).collect::<HashSet<_>>();
+        files.retain(|k, _| chosen.contains(k));
+    }
+    if let Some(prefix) = prefix {
+        files = files
+            .into_iter()
+            .map(|(k, mut v)| {
+                // The default mode depends on the name, so fix it before the name changes.
+                v.mode = Some(v.mode(&k));
+                (format!("{}{}", prefix, k), v)
+            })
+            .collect();
+    }
+    files.keys().try_for_each(|k| validate_key_path(k))?;
+    Ok(files)
+}
+
+/// Writes the keys into `outdir`, which is only modified once every key has been written
+/// successfully. Returns the name and size of each key written, sorted by name.
+async fn write_dir(
+    outdir: &Path,
+    files: Files,
+    concurrency: usize,
+) -> Result<Vec<(String, usize)>, anyhow::Error> {
+    // Everything is written into a staging directory inside outdir first, so that it is on
+    // the same filesystem and the files can be renamed into place.
+    let staging = outdir.join(temp_name());
+    fs::DirBuilder::new()
+        .mode(0o700)
+        .create(&staging)
+        .with_context(|| format!("{}", staging.display()))?;
+    let mut written = files
+        .iter()
+        .map(|(k, v)| (k.clone(), v.contents.len()))
+        .collect::<Vec<_>>();
+    written.sort();
+    let modes = files
+        .iter()
+        .map(|(k, v)| (k.clone(), v.mode(k)))
+        .collect::<HashMap<_, _>>();
+    let result = match write_files(&staging, files, concurrency).await {
+        Ok(mut names) => {
+            names.sort();
+            move_into_place(&staging, outdir, &names)
+        }
+        Err(e) => Err(e),
+    };
+    let _ = fs::remove_dir_all(&staging);
+    result?;
+    for (k, _) in &written {
+        log::debug!("Wrote {} (mode {:o})", outdir.join(k).display(), modes[k]);
+    }
+    Ok(written)
+}
+
+/// A key moved into place by [`move_into_place`], with where the file it replaced was put
+/// aside, if there was one.
+struct Moved {
+    path: PathBuf,
+    replaced: Option<PathBuf>,
+    placed: bool,
+}
+
+/// Moves the keys written into `staging` to the same names in `outdir`, replacing any
+/// existing files, even read-only ones from a previous get. If any move fails, the keys
+/// already moved are taken out again, the files they replaced are put back and the
+/// directories created for them are removed, leaving `outdir` as it was.
+fn move_into_place(staging: &Path, outdir: &Path, names: &[String]) -> Result<(), anyhow::Error> {
+    // Replaced files are set aside on the same filesystem, so that they can be renamed back.
+    let aside = outdir.join(temp_name());
+    fs::DirBuilder::new()
+        .mode(0o700)
+        .create(&aside)
+        .with_context(|| format!("{}", aside.display()))?;
+    let mut moved = Vec::<Moved>::new();
+    let mut created = Vec::<PathBuf>::new();
+    let result = names.iter().enumerate().try_for_each(|(i, k)| {
+        let path = outdir.join(k);
+        let mut missing = path
+            .ancestors()
+            .skip(1)
+            .take_while(|dir| *dir != outdir && !dir.exists())
+            .map(Path::to_path_buf)
+            .collect::<Vec<_>>();
+        create_parent(&path)?;
+        // Deepest last, so that undoing them in reverse removes the deepest first.
+        missing.reverse();
+        created.extend(missing);
+        let replaced = match fs::symlink_metadata(&path) {
+            Ok(metadata) if !metadata.is_dir() => {
+                let replaced = aside.join(i.to_string());
+                fs::rename(&path, &replaced).with_context(|| format!("{}", path.display()))?;
+                Some(replaced)
+            }
+            _ => None,
+        };
+        moved.push(Moved {
+            path: path.clone(),
+            replaced,
+            placed: false,
+        });
+        fs::rename(staging.join(k), &path).with_context(|| format!("{}", path.display()))?;
+        if let Some(last) = moved.last_mut() {
+            last.placed = true;
+        }
+        Ok(())
+    });
+    if result.is_err() {
+        for Moved {
+            path,
+            replaced,
+            placed,
+        } in moved.into_iter().rev()
+        {
+            if placed {
+                let _ = fs::remove_file(&path);
+            }
+            if let Some(replaced) = replaced {
+                if let Err(e) = fs::rename(&replaced, &path) {
+                    log::warn!(
+                        "Warning: could not put {} back after a failed get; it was left at \
+                         {}: {}",
+                        path.display(),
+                        replaced.display(),
+                        e
+                    );
+                    // Keep the file set aside rather than removing it along with the rest.
+                    return result;
+                }
+            }
+        }
+        for dir in created.iter().rev() {
+            let _ = fs::remove_dir(dir);
+        }
+    }
+    let _ = fs::remove_dir_all(&aside);
+    result
+}
+
+/// Writes each key into `dir` with its mode (and mtime, if the entry has one), at most
+/// `concurrency` at a time, returning the names written. Every key is attempted, and all
+/// failures are reported together.
+async fn write_files(
+    dir: &Path,
+    files: Files,
+    concurrency: usize,
+) -> Result<Vec<String>, anyhow::Error> {
+    let semaphore = Arc::new(Semaphore::new(concurrency));
+    let mut tasks = Vec::with_capacity(files.len());
+    for (k, v) in files {
+        let path = dir.join(&k);
+        let mode = v.mode(&k);
+        let permit = semaphore.clone().acquire_owned().await?;
+        let task = task::spawn_blocking(move || {
+            let _permit = permit;
+            create_parent(&path)?;
+            write_file(&path, mode, &v.contents)?;
+            if let Some(mtime) = v.mtime {
+                // Renaming the file into outdir later keeps this time.
+                filetime::set_file_mtime(&path, FileTime::from_unix_time(mtime, 0))
+                    .with_context(|| format!("{}", path.display()))?;
+            }
+            Ok::<_, anyhow::Error>(())
+        });
+        tasks.push((k, task));
+    }
+    let mut names = Vec::with_capacity(tasks.len());
+    let mut errors = Vec::new();
+    for (k, task) in tasks {
+        match task.await? {
+            Ok(()) => names.push(k),
+            Err(e) => errors.push(format!("{:#}", e)),
+        }
+    }
+    if !errors.is_empty() {
+        errors.sort();
+        anyhow::bail!(
+            "Failed to write {} key(s):\n  {}",
+            errors.len(),
+            errors.join("\n  ")
+        );
+    }
+    Ok(names)
+}
+
+/// Creates the directories above a key with a `/` in its name, readable only by the owner.
+fn create_parent(path: &Path) -> Result<(), anyhow::Error> {
+    if let Some(parent) = path.parent() {
+        fs::DirBuilder::new()
+            .recursive(true)
+            .mode(0o700)
+            .create(parent)
+            .with_context(|| format!("{}", parent.display()))?;
+    }
+    Ok(())
+}
+
+/// A name for a temporary file or directory that no other run of ssh-keys picks, even one
+/// running at the same time against the same directory: hidden, with the process id and a
+/// random UUID.
+fn temp_name() -> String {
+    format!(".ssh-keys-{}-{}", process::id(), Uuid::new_v4())
+}
+
+/// Replaces the file at `path` in one step by writing a temporary file next to it and
+/// renaming that into place. The temporary file is removed if anything fails.
+fn write_replacing(path: &Path, mode: u32, contents: &[u8]) -> Result<(), anyhow::Error> {
+    let tmp = path.with_file_name(temp_name());
+    write_file(&tmp, mode, contents)
+        .and_then(|()| fs::rename(&tmp, path).with_context(|| format!("{}", path.display())))
+        .inspect_err(|_| {
+            let _ = fs::remove_file(&tmp);
+        })
+}
+
+/// Creates a single file with the given mode, failing if it already exists.
+fn write_file(path: &Path, mode: u32, contents: &[u8]) -> Result<(), anyhow::Error> {
+    let f = fs::OpenOptions::new()
+        .create_new(true)
+        .write(true)
+        .mode(mode)
+        .open(path)
+        .with_context(|| format!("{}", path.display()))?;
+    let mut writer = io::BufWriter::new(f);
+    writer
+        .write_all(contents)
+        .and_then(|()| writer.flush())
+        .with_context(|| format!("{}", path.display()))?;
+    // Only traced, since keys are written to a staging directory and logged once moved.
+    log::trace!(
+        "Wrote {} ({} bytes, mode {:o})",
+        path.display(),
+        contents.len(),
+        mode
+    );
+    Ok(())
+}
+
+/// Writes the keys as entries of a new tar archive (or to stdout, for `-`), with the modes
+/// they would be given by a get into a directory. Returns the name and size of each key
+/// written, sorted by name. A partly written archive is removed.
+fn write_tar(archive: &Path, files: Files) -> Result<Vec<(String, usize)>, anyhow::Error> {
+    if archive == Path::new("-") {
+        return append_files(Box::new(io::stdout()), archive, &files);
+    }
+    // The archive holds private keys, so only the owner may read it.
+    let f = fs::OpenOptions::new()
+        .create_new(true)
+        .write(true)
+        .mode(0o600)
+        .open(archive)
+        .with_context(|| format!("{}", archive.display()))?;
+    append_files(Box::new(f), archive, &files).inspect_err(|_| {
+        let _ = fs::remove_file(archive);
+    })
+}
+
+/// Writes the keys as a tar archive into `writer`, which writes to `archive`.
+fn append_files(
+    writer: Box<dyn Write>,
+    archive: &Path,
+    files: &Files,
+) -> Result<Vec<(String, usize)>, anyhow::Error> {
+    let mtime = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    let mut keys = files.keys().collect::<Vec<_>>();
+    keys.sort();
+    let mut builder = tar::Builder::new(io::BufWriter::new(writer));
+    let mut written = Vec::with_capacity(keys.len());
+    for k in keys {
+        let v = &files[k];
+        let mut header = tar::Header::new_gnu();
+        header.set_entry_type(tar::EntryType::Regular);
+        header.set_size(v.contents.len() as u64);
+        header.set_mode(v.mode(k));
+        header.set_mtime(mtime);
+        builder
+            .append_data(&mut header, k, v.contents.as_slice())
+            .with_context(|| format!("{}", archive.display()))?;
+        log::debug!("Added {} to {}", k, archive.display());
+        written.push((k.clone(), v.contents.len()));
+    }
+    builder
+        .into_inner()
+        .and_then(|mut writer| writer.flush())
+        .with_context(|| format!("{}", archive.display()))?;
+    Ok(written)
+}
@@ -0,0 +1,115 @@
+use std::io::{self, Write as _};
+
+use owo_colors::{OwoColorize as _, Stream};
+use serde_json::json;
+
+use crate::backend::{fetch_version, SecretsBackend};
+use crate::commands::diff::{changes, Change};
+use crate::commands::versions::{format_timestamp, list_versions};
+use crate::files::Files;
+use crate::output::OutputFormat;
+
+/// Prints the newest `depth` versions of the secret, newest first, each with the keys added,
+/// removed or modified since the version before it. The oldest version shown is compared to
+/// the one before it too, so `depth + 1` versions are downloaded; the very first version of
+/// the secret lists all of its keys as added. Nothing is changed.
+pub async fn history(
+    backend: &dyn SecretsBackend,
+    secret_id: String,
+    depth: usize,
+    output: OutputFormat,
+) -> Result<(), anyhow::Error> {
+    let entries = list_versions(backend, secret_id.clone())
+        .await?
+        .into_iter()
+        .filter(|entry| entry.version_id.is_some())
+        .take(depth + 1)
+        .collect::<Vec<_>>();
+    let mut contents = Vec::with_capacity(entries.len());
+    for entry in &entries {
+        let files =
+            fetch_version(backend, secret_id.clone(), entry.version_id.clone(), None).await?;
+        contents.push(files);
+    }
+    let empty = Files::new();
+    let mut history = Vec::new();
+    for (i, entry) in entries.iter().enumerate().take(depth) {
+        let previous = match contents.get(i + 1) {
+            Some(files) => files,
+            // Fewer versions than asked for exist, so this is the first one.
+            None => &empty,
+        };
+        let changed = changes(&contents[i], previous)
+            .into_iter()
+            .filter(|(_, change)| *change != Change::Unchanged)
+            .collect::<Vec<_>>();
+        history.push((entry, changed));
+    }
+
+    if output == OutputFormat::Json {
+        let value = history
+            .iter()
+            .map(|(entry, changed)| {
+                let keys = |kind: Change| {
+                    changed
+                        .iter()
+                        .filter(|(_, change)| *change == kind)
+                        .map(|(k, _)| k.as_str())
+                        .collect::<Vec<_>>()
+                };
+                json!({
+                    "version_id": entry.version_id,
+                    "created": entry.created_date.map(format_timestamp),
+                    "stages": entry.version_stages.clone().unwrap_or_default(),
+                    "added": keys(Change::Added),
+                    "removed": keys(Change::Removed),
+                    "modified": keys(Change::Modified),
+                })
+            })
+            .collect::<Vec<_>>();
+        println!("{}", json!(value));
+        return Ok(());
+    }
+
+    let stdout = io::stdout();
+    let mut stdout = stdout.lock();
+    for (entry, changed) in history {
+        let created = entry
+            .created_date
+            .map(format_timestamp)
+            .unwrap_or_else(|| "-".to_string());
+        let stages = entry
+            .version_stages
+            .as_ref()
+            .filter(|stages| !stages.is_empty())
+            .map(|stages| stages.join(","))
+            .unwrap_or_else(|| "-".to_string());
+        let id = entry.version_id.as_deref().unwrap_or("-");
+        writeln!(
+            stdout,
+            "{}  {}  {}",
+            id.if_supports_color(Stream::Stdout, |t| t.bold()),
+            created,
+            stages
+        )?;
+        if changed.is_empty() {
+            writeln!(stdout, "  (no changes)")?;
+        }
+        for (k, change) in changed {
+            let line = match change {
+                Change::Added => format!("+ {} (added)", k)
+                    .if_supports_color(Stream::Stdout, |t| t.green())
+                    .to_string(),
+                Change::Removed => format!("- {} (removed)", k)
+                    .if_supports_color(Stream::Stdout, |t| t.red())
+                    .to_string(),
+                Change::Modified => format!("~ {} (modified)", k)
+                    .if_supports_color(Stream::Stdout, |t| t.yellow())
+                    .to_string(),
+                Change::Unchanged => continue,
+            };
+            writeln!(stdout, "  {}", line)?;
+        }
+    }
+    Ok(())
+}
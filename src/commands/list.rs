@@ -0,0 +1,128 @@
+use std::io::{self, Write as _};
+
+use owo_colors::{OwoColorize as _, Stream};
+use rusoto_secretsmanager::{Filter, ListSecretsRequest};
+use serde_json::json;
+
+use crate::backend::{fetch, SecretsBackend};
+use crate::output::OutputFormat;
+
+/// Prints the names of the stored keys, with their sizes if `long` is set.
+pub async fn list(
+    backend: &dyn SecretsBackend,
+    secret_id: String,
+    long: bool,
+    output: OutputFormat,
+) -> Result<(), anyhow::Error> {
+    let files = fetch(backend, secret_id).await?;
+    let mut keys = files.keys().collect::<Vec<_>>();
+    keys.sort();
+    if output == OutputFormat::Json {
+        let value = if long {
+            json!(keys
+                .iter()
+                .map(|k| json!({ "name": k, "size": files[*k].contents.len() }))
+                .collect::<Vec<_>>())
+        } else {
+            json!(keys)
+        };
+        println!("{}", value);
+        return Ok(());
+    }
+    let stdout = io::stdout();
+    let mut stdout = stdout.lock();
+    for k in &keys {
+        let name = k.if_supports_color(Stream::Stdout, |t| t.bold());
+        if long {
+            writeln!(stdout, "{:>8}  {}", files[*k].contents.len(), name)?;
+        } else {
+            writeln!(stdout, "{}", name)?;
+        }
+    }
+    drop(stdout);
+    log::info!("{} key(s) stored", keys.len());
+    Ok(())
+}
+
+/// Prints every secret whose name starts with `prefix`, with the number of keys stored in
+/// each. Secrets that can't be read are listed without a count.
+pub async fn list_matching(
+    backend: &dyn SecretsBackend,
+    prefix: String,
+    output: OutputFormat,
+) -> Result<(), anyhow::Error> {
+    let secret_ids = list_secret_ids(backend, &prefix).await?;
+    let mut counts = Vec::with_capacity(secret_ids.len());
+    for secret_id in &secret_ids {
+        match fetch(backend, secret_id.clone()).await {
+            Ok(files) => counts.push(Some(files.len())),
+            Err(e) => {
+                log::warn!("Warning: could not read {}: {:#}", secret_id, e);
+                counts.push(None);
+            }
+        }
+    }
+    if output == OutputFormat::Json {
+        let secrets = secret_ids
+            .iter()
+            .zip(&counts)
+            .map(|(secret_id, count)| json!({ "name": secret_id, "keys": count }))
+            .collect::<Vec<_>>();
+        println!("{}", json!(secrets));
+        return Ok(());
+    }
+    if secret_ids.is_empty() {
+        log::info!("No secrets start with {}", prefix);
+        return Ok(());
+    }
+    let stdout = io::stdout();
+    let mut stdout = stdout.lock();
+    for (secret_id, count) in secret_ids.iter().zip(&counts) {
+        let name = secret_id.if_supports_color(Stream::Stdout, |t| t.bold());
+        match count {
+            Some(count) => writeln!(stdout, "{:>8}  {}", count, name)?,
+            None => writeln!(stdout, "{:>8}  {}", "?", name)?,
+        }
+    }
+    drop(stdout);
+    log::info!("{} secret(s) start with {}", secret_ids.len(), prefix);
+    Ok(())
+}
+
+/// Lists the names of the secrets starting with `prefix`, sorted, requesting each page of
+/// the listing with the token returned with the previous one.
+pub async fn list_secret_ids(
+    backend: &dyn SecretsBackend,
+    prefix: &str,
+) -> Result<Vec<String>, anyhow::Error> {
+    let mut secret_ids = Vec::new();
+    let mut next_token = None;
+    loop {
+        let request = ListSecretsRequest {
+            filters: Some(vec![Filter {
+                key: Some("name".to_string()),
+                values: Some(vec![prefix.to_string()]),
+            }]),
+            max_results: Some(100),
+            next_token,
+            ..Default::default()
+        };
+        let response = backend.list_secrets(request).await?;
+        secret_ids.extend(
+            response
+                .secret_list
+                .unwrap_or_default()
+                .into_iter()
+                .filter_map(|secret| secret.name),
+        );
+        next_token = response.next_token;
+        if next_token.is_none() {
+            break;
+        }
+    }
+    // Secrets Manager matches the filter regardless of case, but the prefix is exact.
+    secret_ids.retain(|secret_id| secret_id.starts_with(prefix));
+    secret_ids.sort();
+    secret_ids.dedup();
+    Ok(secret_ids)
+}
@@ -0,0 +1,111 @@
+use std::io::{self, IsTerminal as _};
+
+use anyhow::Context as _;
+use glob::Pattern;
+use ssh_key::PrivateKey;
+
+use crate::agent::Agent;
+use crate::backend::{fetch, SecretsBackend};
+use crate::crypt::{decrypt_files, read_passphrase};
+use crate::files::is_public_key_name;
+use crate::keys::{encode_agent_key, parse_private_key};
+
+/// Adds the stored private keys, or those matching `only` if any patterns are given, to the
+/// running ssh-agent without writing them to disk.
+///
+/// Keys put with a passphrase are decrypted with `passphrase`. Private keys encrypted with a
+/// passphrase of their own are decrypted next, asking for it on a terminal. Keys that can't
+/// be read, e.g. older PEM keys, are skipped with a warning.
+pub async fn load(
+    backend: &dyn SecretsBackend,
+    secret_id: String,
+    only: &[Pattern],
+    passphrase: Option<Option<String>>,
+) -> Result<(), anyhow::Error> {
+    // Connect first, so that a missing agent is reported before fetching anything.
+    let mut agent = Agent::connect()?;
+    let passphrase = passphrase
+        .map(|value| read_passphrase(value, false))
+        .transpose()?;
+    let mut files = fetch(backend, secret_id).await?;
+    if !only.is_empty() {
+        files.retain(|k, _| only.iter().any(|p| p.matches(k)));
+        if files.is_empty() {
+            anyhow::bail!("No stored keys match the patterns given with --only");
+        }
+    }
+    decrypt_files(&mut files, passphrase.as_ref())?;
+    let mut names = files
+        .iter()
+        .filter(|(k, v)| {
+            !is_public_key_name(k)
+                && String::from_utf8_lossy(&v.contents).contains("PRIVATE KEY-----")
+        })
+        .map(|(k, _)| k.clone())
+        .collect::<Vec<_>>();
+    if names.is_empty() {
+        anyhow::bail!("No private keys to load");
+    }
+    names.sort();
+    let mut loaded = 0;
+    for name in &names {
+        let key = match parse_private_key(&files[name].contents) {
+            Ok(key) => key,
+            Err(e) => {
+                log::warn!("Warning: not loading {}: {:#}", name, e);
+                continue;
+            }
+        };
+        let key = match decrypt(name, key)? {
+            Some(key) => key,
+            None => continue,
+        };
+        let comment = match key.comment() {
+            "" => name.as_str(),
+            comment => comment,
+        };
+        agent
+            .add_identity(&encode_agent_key(&key)?, comment)
+            .with_context(|| format!("Failed to add {} to the ssh-agent", name))?;
+        log::debug!("Added {} to the ssh-agent", name);
+        loaded += 1;
+    }
+    if loaded == 0 {
+        anyhow::bail!("None of the stored private keys could be loaded");
+    }
+    log::info!("Added {} key(s) to the ssh-agent", loaded);
+    Ok(())
+}
+
+/// Decrypts `key` if it is encrypted, asking for its passphrase. Returns `None`, after a
+/// warning, if the key is to be skipped.
+fn decrypt(name: &str, key: PrivateKey) -> Result<Option<PrivateKey>, anyhow::Error> {
+    if !key.is_encrypted() {
+        return Ok(Some(key));
+    }
+    if !io::stdin().is_terminal() {
+        log::warn!(
+            "Warning: not loading {}: it is encrypted and stdin is not a terminal to ask for \
+             its passphrase",
+            name
+        );
+        return Ok(None);
+    }
+    let prompt = format!("Passphrase for {} (leave empty to skip it): ", name);
+    let passphrase = rpassword::prompt_password(prompt)
+        .with_context(|| format!("Failed to read the passphrase for {}", name))?;
+    if passphrase.is_empty() {
+        log::warn!("Warning: not loading {}", name);
+        return Ok(None);
+    }
+    match key.decrypt(passphrase) {
+        Ok(key) => Ok(Some(key)),
+        Err(_) => {
+            log::warn!(
+                "Warning: not loading {}: could not decrypt it with that passphrase",
+                name
+            );
+            Ok(None)
+        }
+    }
+}
@@ -0,0 +1,87 @@
+use std::io;
+
+use crate::backend::{create, fetch, fetch_formatted, is_not_found, store, SecretsBackend};
+use crate::commands::confirm_change;
+use crate::files::{encode, Files};
+
+/// Moves every stored key into another secret, creating it if it doesn't exist, and with
+/// `delete_source` leaves the source secret empty once the copy has succeeded.
+pub async fn migrate(
+    backend: &dyn SecretsBackend,
+    secret_id: String,
+    dest_secret_id: String,
+    delete_source: bool,
+    yes: bool,
+    dry_run: bool,
+) -> Result<(), anyhow::Error> {
+    if dest_secret_id == secret_id {
+        anyhow::bail!("Cannot migrate {} into itself", secret_id);
+    }
+    let (files, format) = fetch_formatted(backend, secret_id.clone()).await?;
+    let existing = match fetch(backend, dest_secret_id.clone()).await {
+        Ok(existing) => Some(existing),
+        Err(e) if is_not_found(&e) => None,
+        Err(e) => return Err(e),
+    };
+    let mut summary = match &existing {
+        Some(existing) if !existing.is_empty() => format!(
+            "overwrite the {} key(s) in {} with the {} key(s) in {}",
+            existing.len(),
+            dest_secret_id,
+            files.len(),
+            secret_id
+        ),
+        _ => format!(
+            "migrate {} key(s) from {} to {}",
+            files.len(),
+            secret_id,
+            dest_secret_id
+        ),
+    };
+    if delete_source {
+        summary.push_str(&format!(", then delete all keys from {}", secret_id));
+    }
+    if !confirm_change(&mut io::stdout(), &summary, &[], yes, dry_run)? {
+        return Ok(());
+    }
+    // The keys are written the way they are stored in the source, which they are known to
+    // fit in.
+    let payload = encode(&files, format.storage, format.compact, backend.size_limit())?;
+    // Both log the id of the new destination version.
+    match existing {
+        Some(_) => store(backend, dest_secret_id.clone(), payload).await?,
+        None => {
+            create(
+                backend,
+                dest_secret_id.clone(),
+                payload,
+                None,
+                None,
+                None,
+                Vec::new(),
+            )
+            .await?
+        }
+    };
+    log::info!(
+        "Migrated {} key(s) from {} to {}",
+        files.len(),
+        secret_id,
+        dest_secret_id
+    );
+    if delete_source {
+        store(
+            backend,
+            secret_id.clone(),
+            encode(
+                &Files::new(),
+                format.storage,
+                format.compact,
+                backend.size_limit(),
+            )?,
+        )
+        .await?;
+        log::info!("Deleted all keys from {}", secret_id);
+    }
+    Ok(())
+}
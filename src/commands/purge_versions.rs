@@ -0,0 +1,74 @@
+use std::io;
+
+use rusoto_secretsmanager::UpdateSecretVersionStageRequest;
+
+use crate::backend::SecretsBackend;
+use crate::commands::confirm_change;
+use crate::commands::versions::{format_timestamp, list_versions};
+
+/// Staging labels that are never removed, since Secrets Manager uses them for the current
+/// value and for rolling back.
+const PROTECTED_STAGES: &[&str] = &["AWSCURRENT", "AWSPREVIOUS"];
+
+/// Removes the staging labels from all but the newest `keep` labelled versions of the secret,
+/// so that Secrets Manager deprecates and eventually deletes them. Versions labelled
+/// AWSCURRENT or AWSPREVIOUS are always kept.
+pub async fn purge_versions(
+    backend: &dyn SecretsBackend,
+    secret_id: String,
+    keep: usize,
+    yes: bool,
+    dry_run: bool,
+) -> Result<(), anyhow::Error> {
+    let labelled = list_versions(backend, secret_id.clone())
+        .await?
+        .into_iter()
+        .filter(|entry| entry.version_stages.as_ref().is_some_and(|s| !s.is_empty()))
+        .collect::<Vec<_>>();
+    let purge = labelled
+        .into_iter()
+        .enumerate()
+        .filter(|(i, entry)| {
+            let protected = entry
+                .version_stages
+                .iter()
+                .flatten()
+                .any(|stage| PROTECTED_STAGES.contains(&stage.as_str()));
+            *i >= keep && !protected
+        })
+        .filter_map(|(_, entry)| {
+            let id = entry.version_id?;
+            let created = entry
+                .created_date
+                .map(format_timestamp)
+                .unwrap_or_else(|| "-".to_string());
+            Some((id, created, entry.version_stages.unwrap_or_default()))
+        })
+        .collect::<Vec<_>>();
+    if purge.is_empty() {
+        log::info!("No versions of {} to purge", secret_id);
+        return Ok(());
+    }
+    let summary = format!("purge the following versions of {}", secret_id);
+    let details = purge
+        .iter()
+        .map(|(id, created, stages)| format!("  {}  {}  {}", id, created, stages.join(",")))
+        .collect::<Vec<_>>();
+    if !confirm_change(&mut io::stdout(), &summary, &details, yes, dry_run)? {
+        return Ok(());
+    }
+    for (id, _, stages) in &purge {
+        for stage in stages {
+            let request = UpdateSecretVersionStageRequest {
+                secret_id: secret_id.clone(),
+                version_stage: stage.clone(),
+                remove_from_version_id: Some(id.clone()),
+                ..Default::default()
+            };
+            backend.update_secret_version_stage(request).await?;
+        }
+        log::info!("Purged version {} ({})", id, stages.join(","));
+    }
+    log::info!("Purged {} versions of {}", purge.len(), secret_id);
+    Ok(())
+}
@@ -0,0 +1,613 @@
+use std::path::PathBuf;
+
+use anyhow::Context as _;
+use owo_colors::OwoColorize as _;
+use rusoto_core::Region;
+use rusoto_secretsmanager::{Tag, TagResourceRequest, UpdateSecretRequest};
+use serde_json::json;
+use structopt::StructOpt;
+
+use crate::backend::{
+    create as create_secret, fetch, fetch_updated_at, is_not_found, store_updated_at,
+    store_with_token, SecretsBackend,
+};
+use crate::commands::confirm_change;
+use crate::commands::replicas::replicate;
+use crate::commands::versions::format_timestamp;
+use crate::crypt::{encrypt_files, read_passphrase};
+use crate::exit::Failure;
+use crate::files::{encode, is_public_key_name, Files, Payload, Storage};
+use crate::keys::{self, is_encrypted_pem_key, key_pair_matches, validate_key};
+use crate::output::OutputFormat;
+use crate::scan::{scan, scan_tar, ScanOpt};
+
+/// Staging labels that AWS manages itself, which --version-stage only sets with --force-stage.
+const RESERVED_STAGES: &[&str] = &["AWSCURRENT", "AWSPREVIOUS", "AWSPENDING"];
+
+/// Options for the put command
+#[derive(Debug, StructOpt)]
+pub struct PutOpt {
+    /// Directory containing ssh keys to put
+    #[structopt(required_unless = "from-tar")]
+    pub indir: Option<PathBuf>,
+
+    /// Read the keys from the top level of this tar archive (- for stdin) instead of indir
+    #[structopt(long, conflicts_with = "indir")]
+    pub from_tar: Option<PathBuf>,
+
+    /// Keep stored keys that are not in indir instead of deleting them
+    #[structopt(long)]
+    pub merge: bool,
+
+    /// ID or ARN of the KMS key the secret should be encrypted with
+    #[structopt(long)]
+    pub kms_key_id: Option<String>,
+
+    /// Set the description of the secret, as shown in the AWS console
+    #[structopt(long)]
+    pub description: Option<String>,
+
+    /// Which files in indir to read
+    #[structopt(flatten)]
+    pub scan_opt: ScanOpt,
+
+    /// Convert CRLF line endings to LF in each file before storing it; files that aren't
+    /// valid utf-8 are left untouched
+    #[structopt(long)]
+    pub normalize_newlines: bool,
+
+    /// Make each file end with exactly one newline before storing it, so that editors adding
+    /// or removing one don't create new versions; empty files and files that aren't valid
+    /// utf-8 are left untouched
+    #[structopt(long)]
+    pub normalize_trailing_newline: bool,
+
+    /// Remove the comment (often user@host) from each public key, keeping it aside so that
+    /// get --restore-comments can put it back
+    #[structopt(long)]
+    pub strip_comments: bool,
+
+    /// Encrypt the contents of each file with this passphrase before storing it, so that the
+    /// secret only holds ciphertext; asked for on the terminal if given without a value
+    #[structopt(long, require_equals = true)]
+    pub passphrase: Option<Option<String>>,
+
+    /// Don't check that files look like well-formed ssh keys before storing them
+    #[structopt(long)]
+    pub no_validate: bool,
+
+    /// Don't check that each public key (e.g. id_rsa.pub) matches the private key it is named
+    /// after (id_rsa)
+    #[structopt(long)]
+    pub no_pair_check: bool,
+
+    /// Ask for the passphrase of each encrypted PEM private key, so that its public key can
+    /// be checked too; otherwise such keys are skipped
+    #[structopt(long, conflicts_with = "no-pair-check")]
+    pub ask_passphrase: bool,
+
+    /// Refuse to put private keys that are readable or writable by group or others, instead
+    /// of only warning about them
+    #[structopt(long)]
+    pub strict_perms: bool,
+
+    /// Store the JSON in the secret's binary value instead of its string value
+    #[structopt(long)]
+    pub binary: bool,
+
+    /// Store the JSON without indentation or line breaks, to fit more keys under the size
+    /// limit
+    #[structopt(long)]
+    pub compact: bool,
+
+    /// Gzip the secret before storing it in its binary value, to fit more keys under the
+    /// size limit
+    #[structopt(long)]
+    pub compress: bool,
+
+    /// Refuse to upload if the serialized secret is larger than this many bytes; defaults to
+    /// the backend's limit, which is 65536 for Secrets Manager and 4096 for Parameter Store
+    #[structopt(long)]
+    pub max_secret_size: Option<usize>,
+
+    /// Idempotency token for the new version (32 to 64 characters), so that a retried put
+    /// doesn't create a second version; a random UUID is used if not given
+    #[structopt(long, parse(try_from_str = parse_client_request_token))]
+    pub client_request_token: Option<String>,
+
+    /// Create the secret if it doesn't exist yet
+    #[structopt(long)]
+    pub create: bool,
+
+    /// Replicate the secret to this region (e.g. us-west-2) once it exists; regions it is
+    /// already replicated to are skipped (may be repeated)
+    #[structopt(long, number_of_values = 1, requires = "create")]
+    pub replicate_to: Vec<Region>,
+
+    /// Label the new version with this staging label (e.g. PENDING) instead of making it
+    /// current, so that it can be tested before being promoted (may be repeated)
+    #[structopt(long, number_of_values = 1)]
+    pub version_stage: Vec<String>,
+
+    /// Allow --version-stage to use the labels reserved by AWS: AWSCURRENT, AWSPREVIOUS and
+    /// AWSPENDING
+    #[structopt(long, requires = "version-stage")]
+    pub force_stage: bool,
+
+    /// Store a new version even if the keys are unchanged, e.g. to switch to --compress or to
+    /// apply a new --description, --kms-key-id or --tag
+    #[structopt(long)]
+    pub force: bool,
+
+    /// Refuse to upload if the secret was updated after the newest of the files being put
+    /// was last modified, so as not to overwrite a more recent put from elsewhere; the time of
+    /// each put is recorded in the secret's ssh-keys:updated-at tag
+    #[structopt(long)]
+    pub only_if_newer: bool,
+
+    /// Tag to apply to the secret, as key=value (may be repeated)
+    #[structopt(long = "tag", number_of_values = 1, parse(try_from_str = parse_tag))]
+    pub tags: Vec<Tag>,
+}
+
+/// Replaces (or with `merge`, updates) the stored keys with the files in a directory.
+pub async fn put(
+    backend: &dyn SecretsBackend,
+    secret_id: String,
+    yes: bool,
+    dry_run: bool,
+    opt: PutOpt,
+    output: OutputFormat,
+) -> Result<(), anyhow::Error> {
+    let PutOpt {
+        indir,
+        from_tar,
+        merge,
+        kms_key_id,
+        description,
+        scan_opt,
+        normalize_newlines,
+        normalize_trailing_newline,
+        strip_comments,
+        passphrase,
+        no_validate,
+        no_pair_check,
+        ask_passphrase,
+        strict_perms,
+        binary,
+        compact,
+        compress,
+        max_secret_size,
+        client_request_token,
+        create,
+        replicate_to,
+        version_stage,
+        force_stage,
+        force,
+        only_if_newer,
+        tags,
+    } = opt;
+    for stage in &version_stage {
+        if RESERVED_STAGES.contains(&stage.as_str()) && !force_stage {
+            return Err(Failure::Usage.error(format!(
+                "{} is a staging label reserved by AWS; pass --force-stage to use it anyway",
+                stage
+            )));
+        }
+        if stage.is_empty() || stage.len() > 256 {
+            return Err(Failure::Usage.error(format!(
+                "Staging label {:?} must be between 1 and 256 characters long",
+                stage
+            )));
+        }
+    }
+    let passphrase = passphrase
+        .map(|value| read_passphrase(value, true))
+        .transpose()?;
+    let replicate_to = replicate_to
+        .iter()
+        .map(|region| region.name().to_string())
+        .collect::<Vec<_>>();
+    let (mut map, source) = match (indir, from_tar) {
+        (_, Some(archive)) => (scan_tar(&archive, &scan_opt)?, archive),
+        (Some(indir), None) => (scan(&indir, &scan_opt)?, indir),
+        (None, None) => anyhow::bail!("Either indir or --from-tar is required"),
+    };
+    if normalize_newlines {
+        for (k, v) in map.iter_mut() {
+            let normalized = match std::str::from_utf8(&v.contents) {
+                Ok(s) if s.contains("\r\n") => s.replace("\r\n", "\n"),
+                _ => continue,
+            };
+            log::debug!("Converted CRLF line endings to LF in {}", k);
+            v.contents = normalized.into_bytes();
+        }
+    }
+    if normalize_trailing_newline {
+        for (k, v) in map.iter_mut() {
+            let normalized = match std::str::from_utf8(&v.contents) {
+                Ok(s) if !s.is_empty() => format!("{}\n", s.trim_end_matches('\n')),
+                _ => continue,
+            };
+            if normalized.as_bytes() == v.contents.as_slice() {
+                continue;
+            }
+            log::debug!("Made {} end with a single newline", k);
+            v.contents = normalized.into_bytes();
+        }
+    }
+    if strip_comments {
+        for (k, v) in map.iter_mut() {
+            let contents = match std::str::from_utf8(&v.contents) {
+                Ok(s) if is_public_key_name(k) => s,
+                _ => continue,
+            };
+            let (stripped, comments) = keys::strip_comments(contents);
+            if comments.iter().all(String::is_empty) {
+                continue;
+            }
+            log::debug!("Stripped comments from {}", k);
+            v.contents = stripped.into_bytes();
+            v.comments = Some(comments);
+        }
+    }
+    let mut keys = map.keys().collect::<Vec<_>>();
+    keys.sort();
+    for k in keys {
+        let mode = map[k].mode(k);
+        if is_public_key_name(k) || mode & 0o077 == 0 {
+            continue;
+        }
+        let path = source.join(k);
+        if strict_perms {
+            anyhow::bail!(
+                "File {} has mode {:o}, which gives group or others access; chmod 600 it first",
+                path.display(),
+                mode
+            );
+        }
+        log::warn!(
+            "Warning: {} has mode {:o}, which gives group or others access",
+            path.display(),
+            mode
+        );
+    }
+    if !no_validate {
+        let mut keys = map.keys().collect::<Vec<_>>();
+        keys.sort();
+        for k in keys {
+            validate_key(k, &map[k].contents).with_context(|| {
+                format!(
+                    "File {} failed validation (pass --no-validate to skip)",
+                    source.join(k).display()
+                )
+            })?;
+        }
+    }
+    if !no_pair_check {
+        let mut keys = map.keys().collect::<Vec<_>>();
+        keys.sort();
+        for k in keys {
+            let (name, private) = match k
+                .strip_suffix(".pub")
+                .and_then(|name| map.get_key_value(name))
+            {
+                Some(pair) => pair,
+                None => continue,
+            };
+            let (private_path, public_path) = (source.join(name), source.join(k));
+            let mut matches = key_pair_matches(&private.contents, &map[k].contents, None);
+            if matches.is_none() && is_encrypted_pem_key(&private.contents) {
+                if !ask_passphrase {
+                    log::debug!(
+                        "Not checking {} against encrypted {}",
+                        public_path.display(),
+                        private_path.display()
+                    );
+                    continue;
+                }
+                let prompt = format!(
+                    "Passphrase for {} (leave empty to skip checking it): ",
+                    private_path.display()
+                );
+                let passphrase = rpassword::prompt_password(prompt)
+                    .with_context(|| format!("Failed to read the passphrase for {}", name))?;
+                if passphrase.is_empty() {
+                    log::warn!(
+                        "Warning: not checking that {} is the public key of {}",
+                        public_path.display(),
+                        private_path.display()
+                    );
+                    continue;
+                }
+                matches = key_pair_matches(&private.contents, &map[k].contents, Some(&passphrase));
+                if matches.is_none() {
+                    log::warn!(
+                        "Warning: could not decrypt {} with that passphrase; not checking {}",
+                        private_path.display(),
+                        public_path.display()
+                    );
+                    continue;
+                }
+            }
+            // Other private keys that can't be read aren't checked.
+            if matches == Some(false) {
+                log::warn!(
+                    "Warning: {} is not the public key of {} (pass --no-pair-check to skip)",
+                    public_path.display(),
+                    private_path.display()
+                );
+            }
+        }
+    }
+    // Besides being merged with, the stored keys are needed to tell whether anything changed
+    // and to show what will be overwritten and deleted.
+    let stored = if merge || dry_run || only_if_newer || !force || !yes {
+        match fetch(backend, secret_id.clone()).await {
+            Ok(stored) => Some(stored),
+            // There is nothing to merge with in a secret that is about to be created.
+            Err(e) if is_not_found(&e) && (create || !merge) => None,
+            Err(e) => return Err(e),
+        }
+    } else {
+        None
+    };
+    if let (true, Some(_)) = (only_if_newer, &stored) {
+        let updated = fetch_updated_at(backend, secret_id.clone())
+            .await
+            .with_context(|| {
+                format!(
+                    "Cannot tell when {} was last updated, so --only-if-newer can't be checked",
+                    secret_id
+                )
+            })?;
+        let newest = map
+            .values()
+            .filter_map(|v| v.mtime)
+            .max()
+            .with_context(|| {
+                format!(
+                    "None of the files in {} have a modification time to compare with for \
+                     --only-if-newer",
+                    source.display()
+                )
+            })?;
+        match updated {
+            // File times are whole seconds, as is the recorded time.
+            Some(updated) if updated > newest => {
+                return Err(Failure::Conflict.error(format!(
+                    "Secret {} was updated at {}, after the newest file in {} was modified at \
+                     {}; not uploading",
+                    secret_id,
+                    format_timestamp(updated as f64),
+                    source.display(),
+                    format_timestamp(newest as f64)
+                )));
+            }
+            Some(_) => {}
+            None => log::warn!(
+                "Warning: no put has recorded when {} was last updated, so --only-if-newer \
+                 has nothing to compare with",
+                secret_id
+            ),
+        }
+    }
+    if let Some(passphrase) = &passphrase {
+        encrypt_files(&mut map, stored.as_ref(), passphrase)?;
+    }
+    let incoming = map.keys().cloned().collect::<Vec<_>>();
+    if let (true, Some(stored)) = (merge, &stored) {
+        for (k, v) in stored {
+            map.entry(k.clone()).or_insert_with(|| v.clone());
+        }
+    }
+    let storage = if compress {
+        Storage::Compressed
+    } else if binary {
+        Storage::Binary
+    } else {
+        Storage::String
+    };
+    let limit = backend.size_limit();
+    let payload = encode(&map, storage, compact, max_secret_size.unwrap_or(limit))?;
+    let size = payload.len();
+    if !force && stored.as_ref() == Some(&map) {
+        if output == OutputFormat::Json {
+            println!("{}", json!({ "changed": false }));
+        } else {
+            log::info!("No changes.");
+        }
+        if !replicate_to.is_empty() && !dry_run {
+            replicate(backend, &secret_id, &replicate_to).await?;
+        }
+        return Ok(());
+    }
+    {
+        let empty = Files::new();
+        let stored = stored.as_ref().unwrap_or(&empty);
+        let summary = if merge {
+            "merge the following into ssh-keys"
+        } else {
+            "override ssh-keys with the following"
+        };
+        let mut details = Vec::new();
+        let mut keys = map.keys().chain(stored.keys()).collect::<Vec<_>>();
+        keys.sort();
+        keys.dedup();
+        let stream = output.messages_stream();
+        for k in keys {
+            let line = match (stored.get(k), map.get(k)) {
+                (None, Some(new)) => format!("  + {} (add, {} bytes)", k, new.contents.len())
+                    .if_supports_color(stream, |t| t.green())
+                    .to_string(),
+                (Some(_), Some(_)) if !incoming.contains(k) => format!("  = {} (untouched)", k),
+                (Some(old), Some(new)) => format!(
+                    "  ~ {} (overwrite, {} -> {} bytes)",
+                    k,
+                    old.contents.len(),
+                    new.contents.len()
+                )
+                .if_supports_color(stream, |t| t.yellow())
+                .to_string(),
+                (Some(_), None) => format!("  - {} (delete)", k)
+                    .if_supports_color(stream, |t| t.red())
+                    .to_string(),
+                (None, None) => unreachable!(),
+            };
+            details.push(line);
+        }
+        if !confirm_change(&mut *output.messages(), summary, &details, yes, dry_run)? {
+            // Only a dry run gets here. The payload is printed so it can be diffed against
+            // the current secret.
+            println!("{}", serde_json::to_string_pretty(&map)?);
+            return Ok(());
+        }
+    }
+    let version_id = match update_and_store(
+        backend,
+        secret_id.clone(),
+        kms_key_id.clone(),
+        description.clone(),
+        payload.clone(),
+        client_request_token.clone(),
+        version_stage.clone(),
+    )
+    .await
+    {
+        Ok(version_id) => {
+            if !tags.is_empty() {
+                let request = TagResourceRequest {
+                    secret_id: secret_id.clone(),
+                    tags,
+                };
+                backend.tag_resource(request).await?;
+            }
+            version_id
+        }
+        Err(e) if is_not_found(&e) => {
+            if !create {
+                return Err(e.context(format!(
+                    "Secret {} does not exist; pass --create to create it, or create it manually",
+                    secret_id
+                )));
+            }
+            if !version_stage.is_empty() {
+                return Err(e.context(format!(
+                    "Secret {} does not exist, and its first version is always current; \
+                     create it without --version-stage first",
+                    secret_id
+                )));
+            }
+            // Tags are applied as part of creating the secret.
+            create_secret(
+                backend,
+                secret_id.clone(),
+                payload,
+                client_request_token,
+                kms_key_id,
+                description,
+                tags,
+            )
+            .await?
+        }
+        Err(e) => return Err(e),
+    };
+    // A staged version doesn't update the secret until it is made current.
+    if version_stage.is_empty() {
+        if let Err(e) = store_updated_at(backend, secret_id.clone()).await {
+            log::warn!(
+                "Warning: could not record when {} was updated, so put --only-if-newer can't \
+                 check against this put: {:#}",
+                secret_id,
+                e
+            );
+        }
+    }
+    if !replicate_to.is_empty() {
+        replicate(backend, &secret_id, &replicate_to).await?;
+    }
+    let mut largest = map
+        .iter()
+        .map(|(k, v)| (k.as_str(), v.contents.len()))
+        .collect::<Vec<_>>();
+    largest.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(b.0)));
+    largest.truncate(3);
+    let percent = size as f64 * 100.0 / limit as f64;
+    if output == OutputFormat::Json {
+        let largest = largest
+            .iter()
+            .map(|(k, size)| json!({ "name": k, "size": size }))
+            .collect::<Vec<_>>();
+        println!(
+            "{}",
+            json!({
+                "changed": true,
+                "version_id": version_id,
+                "size": size,
+                "size_limit": limit,
+                "size_percent": percent,
+                "largest": largest,
+            })
+        );
+    } else {
+        log::info!(
+            "Secret size: {} bytes ({:.1}% of the {} byte limit)",
+            size,
+            percent,
+            limit
+        );
+        let largest = largest
+            .iter()
+            .map(|(k, size)| format!("{} ({} bytes)", k, size))
+            .collect::<Vec<_>>();
+        log::info!("Largest keys: {}", largest.join(", "));
+    }
+    Ok(())
+}
+
+/// Stores a new version of an existing secret, first switching it to `kms_key_id` and setting
+/// its `description` if given, and returns the id of the new version.
+async fn update_and_store(
+    backend: &dyn SecretsBackend,
+    secret_id: String,
+    kms_key_id: Option<String>,
+    description: Option<String>,
+    payload: Payload,
+    token: Option<String>,
+    version_stages: Vec<String>,
+) -> Result<Option<String>, anyhow::Error> {
+    if kms_key_id.is_some() || description.is_some() {
+        // The KMS key belongs to the secret rather than to a version, so it has to be set
+        // before the new value is put for that value to be encrypted with it.
+        let request = UpdateSecretRequest {
+            description,
+            kms_key_id,
+            secret_id: secret_id.clone(),
+            ..Default::default()
+        };
+        backend.update_secret(request).await?;
+    }
+    store_with_token(backend, secret_id, payload, token, version_stages).await
+}
+
+/// Checks that a client request token given on the command line has a length AWS accepts.
+fn parse_client_request_token(s: &str) -> Result<String, anyhow::Error> {
+    if !(32..=64).contains(&s.len()) {
+        anyhow::bail!(
+            "Client request token must be between 32 and 64 characters long but is {}",
+            s.len()
+        );
+    }
+    Ok(s.to_string())
+}
+
+/// Parses a `key=value` pair given on the command line into a secret tag.
+fn parse_tag(s: &str) -> Result<Tag, anyhow::Error> {
+    match s.split_once('=') {
+        Some((key, value)) if !key.is_empty() => Ok(Tag {
+            key: Some(key.to_string()),
+            value: Some(value.to_string()),
+        }),
+        _ => anyhow::bail!("Expected a tag of the form key=value but got {:?}", s),
+    }
+}
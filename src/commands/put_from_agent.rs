@@ -0,0 +1,85 @@
+use std::io;
+
+use sha2::{Digest as _, Sha256};
+
+use crate::agent::{identities, Identity};
+use crate::backend::{fetch_formatted, store, SecretsBackend};
+use crate::commands::confirm_change;
+use crate::files::{encode, Entry};
+
+/// Adds the public keys loaded into the running ssh-agent to the stored secret, as
+/// `agent-<type>-<hash>.pub` where the hash is the start of the SHA256 digest of the key.
+///
+/// The agent never gives out private keys, so only the public keys and their comments are
+/// stored. Other stored keys are kept.
+pub async fn put_from_agent(
+    backend: &dyn SecretsBackend,
+    secret_id: String,
+    yes: bool,
+    dry_run: bool,
+) -> Result<(), anyhow::Error> {
+    let identities = identities()?;
+    if identities.is_empty() {
+        anyhow::bail!("The ssh-agent has no keys loaded; add them with ssh-add");
+    }
+    let (mut files, format) = fetch_formatted(backend, secret_id.clone()).await?;
+    let mut details = Vec::new();
+    let mut changed = 0;
+    for identity in &identities {
+        let name = agent_key_name(identity)?;
+        let contents = identity.public_key_line()?.into_bytes();
+        let action = match files.get(&name) {
+            Some(existing) if existing.contents == contents => continue,
+            Some(_) => format!("  ~ {} (overwrite)", name),
+            None => format!("  + {} (add)", name),
+        };
+        details.push(action);
+        files.insert(
+            name,
+            Entry {
+                contents,
+                mode: None,
+                mtime: None,
+                comments: None,
+                encrypted: false,
+            },
+        );
+        changed += 1;
+    }
+    if changed == 0 {
+        log::info!("No changes.");
+        return Ok(());
+    }
+    let summary = "store the following public keys from the ssh-agent in ssh-keys";
+    if !confirm_change(&mut io::stdout(), summary, &details, yes, dry_run)? {
+        return Ok(());
+    }
+    store(
+        backend,
+        secret_id,
+        encode(&files, format.storage, format.compact, backend.size_limit())?,
+    )
+    .await?;
+    log::info!("Stored {} public key(s) from the ssh-agent", changed);
+    Ok(())
+}
+
+/// The name a key from the agent is stored under, e.g. `agent-ed25519-1a2b3c4d.pub`. It
+/// only depends on the key, so putting the same key again replaces it.
+fn agent_key_name(identity: &Identity) -> Result<String, anyhow::Error> {
+    let key_type = identity.key_type()?;
+    let key_type = key_type.strip_prefix("ssh-").unwrap_or(&key_type);
+    let key_type = key_type
+        .chars()
+        .map(|c| match c {
+            'a'..='z' | 'A'..='Z' | '0'..='9' | '-' => c,
+            _ => '_',
+        })
+        .collect::<String>();
+    let digest = Sha256::digest(&identity.blob);
+    let hash = digest[..4]
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect::<String>();
+    Ok(format!("agent-{}-{}.pub", key_type, hash))
+}
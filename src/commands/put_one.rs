@@ -0,0 +1,60 @@
+use std::io::{self, Read as _};
+
+use anyhow::Context as _;
+
+use crate::backend::{fetch_formatted, store, SecretsBackend};
+use crate::commands::confirm_change;
+use crate::files::{encode, validate_key_path, Entry};
+use crate::keys::validate_key;
+
+/// Adds a single key read from stdin to the stored secret.
+///
+/// Since stdin holds the key, this can't ask for confirmation, so it fails without `yes` or
+/// `dry_run` before reading anything.
+pub async fn put_one(
+    backend: &dyn SecretsBackend,
+    secret_id: String,
+    name: String,
+    yes: bool,
+    dry_run: bool,
+) -> Result<(), anyhow::Error> {
+    if !yes && !dry_run {
+        anyhow::bail!(
+            "Cannot ask for confirmation because the key is read from stdin; pass --yes, or \
+             --dry-run to see what would change"
+        );
+    }
+    validate_key_path(&name)?;
+    let mut contents = Vec::new();
+    io::stdin().read_to_end(&mut contents)?;
+    if contents.is_empty() {
+        anyhow::bail!("No key was given on stdin");
+    }
+    validate_key(&name, &contents).with_context(|| format!("Key {} failed validation", name))?;
+    let (mut files, format) = fetch_formatted(backend, secret_id.clone()).await?;
+    let summary = if files.contains_key(&name) {
+        format!("overwrite {} in ssh-keys", name)
+    } else {
+        format!("add {} to ssh-keys", name)
+    };
+    if !confirm_change(&mut io::stdout(), &summary, &[], yes, dry_run)? {
+        return Ok(());
+    }
+    files.insert(
+        name,
+        Entry {
+            contents,
+            mode: None,
+            mtime: None,
+            comments: None,
+            encrypted: false,
+        },
+    );
+    store(
+        backend,
+        secret_id,
+        encode(&files, format.storage, format.compact, backend.size_limit())?,
+    )
+    .await?;
+    Ok(())
+}
@@ -0,0 +1,54 @@
+use std::io;
+
+use crate::backend::{fetch_formatted, store, SecretsBackend};
+use crate::commands::{confirm_change, missing_key};
+use crate::exit::Failure;
+use crate::files::encode;
+
+/// Moves a stored key to a new name without changing its contents.
+pub async fn rename(
+    backend: &dyn SecretsBackend,
+    secret_id: String,
+    from: String,
+    to: String,
+    force: bool,
+    yes: bool,
+    dry_run: bool,
+) -> Result<(), anyhow::Error> {
+    let (mut files, format) = fetch_formatted(backend, secret_id.clone()).await?;
+    if from == to {
+        anyhow::bail!("Cannot rename {} to itself", from);
+    }
+    if files.contains_key(&to) && !force {
+        return Err(Failure::Conflict.error(format!(
+            "Key {} already exists in secret; pass --force to overwrite it",
+            to
+        )));
+    }
+    let mut entry = match files.remove(&from) {
+        Some(entry) => entry,
+        None => return Err(missing_key(&from, &files)),
+    };
+    // Keys without a recorded mode get a default based on their name, which could change with
+    // the new name, so the mode is pinned to what the key had before.
+    entry.mode = Some(entry.mode(&from));
+    let summary = if files.contains_key(&to) {
+        format!(
+            "rename {} to {} in ssh-keys, overwriting the existing {}",
+            from, to, to
+        )
+    } else {
+        format!("rename {} to {} in ssh-keys", from, to)
+    };
+    if !confirm_change(&mut io::stdout(), &summary, &[], yes, dry_run)? {
+        return Ok(());
+    }
+    files.insert(to, entry);
+    store(
+        backend,
+        secret_id,
+        encode(&files, format.storage, format.compact, backend.size_limit())?,
+    )
+    .await?;
+    Ok(())
+}
@@ -0,0 +1,127 @@
+use std::io::{self, Write as _};
+
+use rusoto_secretsmanager::{
+    DescribeSecretRequest, ReplicaRegionType, ReplicateSecretToRegionsRequest,
+    ReplicationStatusType,
+};
+use serde_json::json;
+
+use crate::backend::SecretsBackend;
+use crate::output::OutputFormat;
+
+/// Prints the regions the secret is replicated to, with the status of each replica.
+pub async fn replicas(
+    backend: &dyn SecretsBackend,
+    secret_id: String,
+    output: OutputFormat,
+) -> Result<(), anyhow::Error> {
+    let request = DescribeSecretRequest {
+        secret_id: secret_id.clone(),
+    };
+    let response = backend.describe_secret(request).await?;
+    let statuses = response.replication_status.unwrap_or_default();
+    if output == OutputFormat::Json {
+        let replicas = statuses
+            .iter()
+            .map(|status| {
+                json!({
+                    "region": status.region,
+                    "status": status.status,
+                    "message": status.status_message,
+                })
+            })
+            .collect::<Vec<_>>();
+        println!(
+            "{}",
+            json!({ "primary_region": response.primary_region, "replicas": replicas })
+        );
+        return Ok(());
+    }
+    if let Some(primary) = &response.primary_region {
+        // The replicas of a replica are managed through its primary.
+        log::info!("{} is a replica of the secret in {}", secret_id, primary);
+    }
+    if statuses.is_empty() {
+        log::info!("{} isn't replicated to any region", secret_id);
+        return Ok(());
+    }
+    let stdout = io::stdout();
+    let mut stdout = stdout.lock();
+    for status in &statuses {
+        let region = status.region.as_deref().unwrap_or("-");
+        let state = status.status.as_deref().unwrap_or("-");
+        match &status.status_message {
+            Some(message) => writeln!(stdout, "{}  {}  {}", region, state, message)?,
+            None => writeln!(stdout, "{}  {}", region, state)?,
+        }
+    }
+    Ok(())
+}
+
+/// Makes sure the secret is replicated to each of `regions`. Regions it is already
+/// replicated to are skipped rather than failing the request, and replicas that fail to be
+/// created, such as because the region already has a secret of the same name, are warned
+/// about.
+pub(crate) async fn replicate(
+    backend: &dyn SecretsBackend,
+    secret_id: &str,
+    regions: &[String],
+) -> Result<(), anyhow::Error> {
+    let request = DescribeSecretRequest {
+        secret_id: secret_id.to_string(),
+    };
+    let existing = backend
+        .describe_secret(request)
+        .await?
+        .replication_status
+        .unwrap_or_default()
+        .into_iter()
+        .filter_map(|status| status.region)
+        .collect::<Vec<_>>();
+    let mut add = Vec::new();
+    for region in regions {
+        if existing.contains(region) {
+            log::info!("{} is already replicated to {}", secret_id, region);
+        } else if !add.contains(region) {
+            add.push(region.clone());
+        }
+    }
+    if add.is_empty() {
+        return Ok(());
+    }
+    let request = ReplicateSecretToRegionsRequest {
+        secret_id: secret_id.to_string(),
+        add_replica_regions: add
+            .into_iter()
+            .map(|region| ReplicaRegionType {
+                region: Some(region),
+                kms_key_id: None,
+            })
+            .collect(),
+        force_overwrite_replica_secret: None,
+    };
+    let response = backend.replicate_secret_to_regions(request).await?;
+    for status in response.replication_status.unwrap_or_default() {
+        report_replica(secret_id, &status);
+    }
+    Ok(())
+}
+
+/// Logs the outcome of starting to replicate the secret to one region.
+fn report_replica(secret_id: &str, status: &ReplicationStatusType) {
+    let region = status.region.as_deref().unwrap_or("-");
+    match status.status.as_deref() {
+        Some("Failed") => log::warn!(
+            "Warning: failed to replicate {} to {}: {}",
+            secret_id,
+            region,
+            status.status_message.as_deref().unwrap_or("unknown error")
+        ),
+        state => log::info!(
+            "Replicating {} to {} ({})",
+            secret_id,
+            region,
+            state.unwrap_or("-")
+        ),
+    }
+}
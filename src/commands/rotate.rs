@@ -0,0 +1,85 @@
+use std::io;
+
+use crate::backend::{fetch_formatted, store, SecretsBackend};
+use crate::commands::confirm_change;
+use crate::files::{encode, Entry};
+use crate::keys::{generate_key_pair, parse_public_key, KeyType};
+
+/// Replaces the key pair stored as `name` and `name.pub` with a newly generated one, keeping
+/// the previous pair as `name.old` and `name.old.pub`, and prints the new public key.
+pub async fn rotate(
+    backend: &dyn SecretsBackend,
+    secret_id: String,
+    name: String,
+    key_type: KeyType,
+    yes: bool,
+    dry_run: bool,
+) -> Result<(), anyhow::Error> {
+    let (mut files, format) = fetch_formatted(backend, secret_id.clone()).await?;
+    let public_name = format!("{}.pub", name);
+    // The old public key keeps the .pub suffix, so that it is still treated as one.
+    let replaced = [
+        (name.clone(), format!("{}.old", name)),
+        (public_name.clone(), format!("{}.old.pub", name)),
+    ]
+    .iter()
+    .filter(|(k, _)| files.contains_key(k))
+    .cloned()
+    .collect::<Vec<_>>();
+    let summary = if replaced.is_empty() {
+        format!("add a new key pair {} to {}", name, secret_id)
+    } else {
+        format!(
+            "replace {} in {}, keeping the current keys as {}",
+            replaced
+                .iter()
+                .map(|(k, _)| k.as_str())
+                .collect::<Vec<_>>()
+                .join(" and "),
+            secret_id,
+            replaced
+                .iter()
+                .map(|(_, old)| old.as_str())
+                .collect::<Vec<_>>()
+                .join(" and ")
+        )
+    };
+    if !confirm_change(&mut io::stdout(), &summary, &[], yes, dry_run)? {
+        return Ok(());
+    }
+    let (private, public) = generate_key_pair(key_type, &name)?;
+    for (k, old_name) in replaced {
+        let old = files.remove(&k).expect("Cannot fail");
+        files.insert(old_name, old);
+    }
+    let fingerprint = parse_public_key(public.trim_end())?.fingerprint();
+    files.insert(
+        name.clone(),
+        Entry {
+            contents: private.into_bytes(),
+            mode: Some(0o600),
+            mtime: None,
+            comments: None,
+            encrypted: false,
+        },
+    );
+    files.insert(
+        public_name,
+        Entry {
+            contents: public.clone().into_bytes(),
+            mode: Some(0o644),
+            mtime: None,
+            comments: None,
+            encrypted: false,
+        },
+    );
+    store(
+        backend,
+        secret_id,
+        encode(&files, format.storage, format.compact, backend.size_limit())?,
+    )
+    .await?;
+    print!("{}", public);
+    log::info!("Fingerprint of {}: {}", name, fingerprint);
+    Ok(())
+}
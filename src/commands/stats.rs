@@ -0,0 +1,141 @@
+use std::collections::BTreeMap;
+use std::io::{self, Write as _};
+
+use rusoto_secretsmanager::GetSecretValueRequest;
+use serde_json::json;
+
+use crate::backend::SecretsBackend;
+use crate::files::{decode, is_public_key_name};
+use crate::keys::parse_public_key;
+use crate::output::OutputFormat;
+
+/// Prints a summary of the stored keys: how many there are of each kind, how much of the
+/// size limit the secret uses, the largest and smallest keys and the types of the public
+/// keys. Keys put with a passphrase are counted as encrypted, as their kind and type can't be
+/// told without decrypting them.
+pub async fn stats(
+    backend: &dyn SecretsBackend,
+    secret_id: String,
+    output: OutputFormat,
+) -> Result<(), anyhow::Error> {
+    let request = GetSecretValueRequest {
+        secret_id,
+        ..Default::default()
+    };
+    let response = backend.get_secret_value(request).await?;
+    // The size counts against the limit as stored, i.e. after any compression.
+    let size = match (&response.secret_string, &response.secret_binary) {
+        (Some(s), _) => s.len(),
+        (None, Some(b)) => b.len(),
+        (None, None) => 0,
+    };
+    let files = decode(response.secret_string, response.secret_binary.as_deref())?;
+    let mut keys = files.keys().collect::<Vec<_>>();
+    keys.sort();
+    let (mut public, mut private, mut other, mut encrypted) = (0, 0, 0, 0);
+    let mut key_types = BTreeMap::<String, usize>::new();
+    for k in &keys {
+        let contents = String::from_utf8_lossy(&files[*k].contents);
+        if files[*k].encrypted {
+            encrypted += 1;
+        } else if is_public_key_name(k) {
+            public += 1;
+            // Each line of a .pub file can hold a key, as in authorized_keys.
+            for line in contents.lines().map(str::trim) {
+                if line.starts_with('#') || parse_public_key(line).is_err() {
+                    continue;
+                }
+                let key_type = line.split_whitespace().next().unwrap_or_default();
+                *key_types.entry(key_kind(key_type)).or_default() += 1;
+            }
+        } else if k.starts_with("id_") || contents.contains("PRIVATE KEY-----") {
+            private += 1;
+        } else {
+            other += 1;
+        }
+    }
+    let mut by_size = keys
+        .iter()
+        .map(|k| (k.as_str(), files[*k].contents.len()))
+        .collect::<Vec<_>>();
+    // Largest first, and by name among keys of the same size.
+    by_size.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(b.0)));
+    let largest = by_size.first().copied();
+    let smallest = by_size.iter().min_by_key(|(_, size)| *size).copied();
+    let limit = backend.size_limit();
+    let percent = size as f64 * 100.0 / limit as f64;
+    if output == OutputFormat::Json {
+        let key_json = |key: Option<(&str, usize)>| {
+            key.map(|(name, size)| json!({ "name": name, "size": size }))
+        };
+        println!(
+            "{}",
+            json!({
+                "keys": keys.len(),
+                "public": public,
+                "private": private,
+                "other": other,
+                "encrypted": encrypted,
+                "size": size,
+                "size_limit": limit,
+                "size_percent": percent,
+                "largest": key_json(largest),
+                "smallest": key_json(smallest),
+                "key_types": key_types,
+            })
+        );
+        return Ok(());
+    }
+    let describe = |key: Option<(&str, usize)>| {
+        key.map_or_else(
+            || "-".to_string(),
+            |(name, size)| format!("{} ({} bytes)", name, size),
+        )
+    };
+    let types = if key_types.is_empty() {
+        "-".to_string()
+    } else {
+        key_types
+            .iter()
+            .map(|(key_type, count)| format!("{} ({})", key_type, count))
+            .collect::<Vec<_>>()
+            .join(", ")
+    };
+    let stdout = io::stdout();
+    let mut stdout = stdout.lock();
+    write!(
+        stdout,
+        "Keys:       {} ({} public, {} private",
+        keys.len(),
+        public,
+        private
+    )?;
+    if other > 0 {
+        write!(stdout, ", {} other", other)?;
+    }
+    if encrypted > 0 {
+        write!(stdout, ", {} encrypted", encrypted)?;
+    }
+    writeln!(stdout, ")")?;
+    writeln!(
+        stdout,
+        "Size:       {} bytes ({:.1}% of the {} byte limit)",
+        size, percent, limit
+    )?;
+    writeln!(stdout, "Largest:    {}", describe(largest))?;
+    writeln!(stdout, "Smallest:   {}", describe(smallest))?;
+    writeln!(stdout, "Key types:  {}", types)?;
+    Ok(())
+}
+
+/// The short name of a public key type, e.g. `rsa` for `ssh-rsa` and `ecdsa` for any of the
+/// `ecdsa-sha2-*` curves.
+fn key_kind(key_type: &str) -> String {
+    if key_type.starts_with("ecdsa-sha2-") {
+        return "ecdsa".to_string();
+    }
+    key_type
+        .strip_prefix("ssh-")
+        .unwrap_or(key_type)
+        .to_string()
+}
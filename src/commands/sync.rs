@@ -0,0 +1,65 @@
+use std::io;
+use std::path::Path;
+
+use crate::backend::{fetch_formatted, store, SecretsBackend};
+use crate::commands::confirm_change;
+use crate::commands::diff::{changes, Change};
+use crate::crypt::{decrypt_files, encrypt_files, read_passphrase};
+use crate::files::encode;
+use crate::scan::{scan, ScanOpt};
+
+/// Makes the stored keys match the files in a directory, only writing a new version of the
+/// secret if something actually changed.
+///
+/// Keys encrypted by put --passphrase are compared after decrypting them with `passphrase`,
+/// and with one given every key is stored encrypted, as by put --passphrase.
+pub async fn sync(
+    backend: &dyn SecretsBackend,
+    secret_id: String,
+    indir: &Path,
+    scan_opt: &ScanOpt,
+    passphrase: Option<Option<String>>,
+    yes: bool,
+    dry_run: bool,
+) -> Result<(), anyhow::Error> {
+    let passphrase = passphrase
+        .map(|value| read_passphrase(value, false))
+        .transpose()?;
+    let mut local = scan(indir, scan_opt)?;
+    let (stored, format) = fetch_formatted(backend, secret_id.clone()).await?;
+    let mut decrypted = stored.clone();
+    decrypt_files(&mut decrypted, passphrase.as_ref())?;
+    let delta = changes(&local, &decrypted)
+        .into_iter()
+        .filter(|(_, change)| *change != Change::Unchanged)
+        .collect::<Vec<_>>();
+    if delta.is_empty() {
+        log::info!("0 changes, nothing to do");
+        return Ok(());
+    }
+    let summary = format!("make the following {} change(s) to ssh-keys", delta.len());
+    let details = delta
+        .iter()
+        .map(|(k, change)| {
+            let action = match change {
+                Change::Added => "add",
+                Change::Removed => "delete",
+                _ => "update",
+            };
+            format!("  - {} ({})", k, action)
+        })
+        .collect::<Vec<_>>();
+    if !confirm_change(&mut io::stdout(), &summary, &details, yes, dry_run)? {
+        return Ok(());
+    }
+    if let Some(passphrase) = &passphrase {
+        encrypt_files(&mut local, Some(&stored), passphrase)?;
+    }
+    store(
+        backend,
+        secret_id,
+        encode(&local, format.storage, format.compact, backend.size_limit())?,
+    )
+    .await?;
+    Ok(())
+}
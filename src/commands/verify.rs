@@ -0,0 +1,45 @@
+use std::path::Path;
+
+use crate::backend::{fetch, SecretsBackend};
+use crate::commands::diff::{changes, Change};
+use crate::crypt::{decrypt_files, read_passphrase};
+use crate::scan::{scan, ScanOpt};
+
+/// Checks that the files in a directory match the stored keys, reporting each mismatch.
+/// Returns whether everything matched. Nothing is written locally or uploaded. Keys put with
+/// a passphrase are decrypted with `passphrase` first.
+pub async fn verify(
+    backend: &dyn SecretsBackend,
+    secret_id: String,
+    dir: &Path,
+    scan_opt: &ScanOpt,
+    passphrase: Option<Option<String>>,
+) -> Result<bool, anyhow::Error> {
+    let local = scan(dir, scan_opt)?;
+    let passphrase = passphrase
+        .map(|value| read_passphrase(value, false))
+        .transpose()?;
+    let mut stored = fetch(backend, secret_id).await?;
+    decrypt_files(&mut stored, passphrase.as_ref())?;
+    let mut mismatches = 0;
+    let mut matches = 0;
+    for (k, change) in changes(&local, &stored) {
+        let problem = match change {
+            Change::Added => "only exists locally",
+            Change::Removed => "is missing locally",
+            Change::Modified => "differs from the stored key",
+            Change::Unchanged => {
+                matches += 1;
+                continue;
+            }
+        };
+        mismatches += 1;
+        println!("{} {}", dir.join(&k).display(), problem);
+    }
+    if mismatches == 0 {
+        log::info!("All {} key(s) match", matches);
+    } else {
+        log::info!("{} mismatch(es), {} key(s) match", mismatches, matches);
+    }
+    Ok(mismatches == 0)
+}
@@ -0,0 +1,69 @@
+use std::cmp::Ordering;
+use std::io::{self, Write as _};
+
+use chrono::{SecondsFormat, TimeZone as _, Utc};
+use rusoto_secretsmanager::{ListSecretVersionIdsRequest, SecretVersionsListEntry};
+
+use crate::backend::SecretsBackend;
+
+/// Prints the secret's versions, newest first, with their staging labels.
+pub async fn versions(
+    backend: &dyn SecretsBackend,
+    secret_id: String,
+) -> Result<(), anyhow::Error> {
+    let entries = list_versions(backend, secret_id).await?;
+    let stdout = io::stdout();
+    let mut stdout = stdout.lock();
+    for entry in entries {
+        let created = entry
+            .created_date
+            .map(format_timestamp)
+            .unwrap_or_else(|| "-".to_string());
+        let stages = entry
+            .version_stages
+            .filter(|stages| !stages.is_empty())
+            .map(|stages| stages.join(","))
+            .unwrap_or_else(|| "-".to_string());
+        let id = entry.version_id.unwrap_or_else(|| "-".to_string());
+        stdout.write_all(format!("{}  {}  {}\n", id, created, stages).as_bytes())?;
+    }
+    Ok(())
+}
+
+/// Lists every version of the secret, including deprecated ones, newest first.
+///
+/// Each page is requested with the token returned with the previous one, so when `backend`
+/// retries a throttled request it asks for the same page again rather than starting over.
+pub async fn list_versions(
+    backend: &dyn SecretsBackend,
+    secret_id: String,
+) -> Result<Vec<SecretVersionsListEntry>, anyhow::Error> {
+    let mut entries = Vec::new();
+    let mut next_token = None;
+    loop {
+        let request = ListSecretVersionIdsRequest {
+            include_deprecated: Some(true),
+            next_token,
+            secret_id: secret_id.clone(),
+            ..Default::default()
+        };
+        let response = backend.list_secret_version_ids(request).await?;
+        entries.extend(response.versions.unwrap_or_default());
+        next_token = response.next_token;
+        if next_token.is_none() {
+            break;
+        }
+    }
+    entries.sort_by(|a, b| {
+        b.created_date
+            .partial_cmp(&a.created_date)
+            .unwrap_or(Ordering::Equal)
+    });
+    Ok(entries)
+}
+
+/// Formats a timestamp returned by AWS (seconds since the Unix epoch) as RFC 3339.
+pub(crate) fn format_timestamp(seconds: f64) -> String {
+    let dt = Utc.timestamp(seconds.trunc() as i64, (seconds.fract() * 1e9) as u32);
+    dt.to_rfc3339_opts(SecondsFormat::Secs, true)
+}
@@ -0,0 +1,100 @@
+//! Defaults for command line options read from a config file.
+
+use std::collections::BTreeMap;
+use std::env;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use anyhow::Context as _;
+use structopt::clap::ArgMatches;
+
+/// Top-level options that the config file may set, by their long flag name.
+const SETTINGS: &[&str] = &[
+    "assume-role-arn",
+    "aws-profile",
+    "aws-region",
+    "backend",
+    "cache-ttl",
+    "color",
+    "credentials",
+    "endpoint-url",
+    "external-id",
+    "max-retries",
+    "mfa-serial",
+    "output",
+    "path",
+    "role-session-name",
+    "secret-id",
+    "timeout",
+];
+
+/// The config file read when none is given: `$XDG_CONFIG_HOME/ssh-keys/config.toml`, or
+/// `~/.config/ssh-keys/config.toml`.
+pub fn default_config_path() -> Option<PathBuf> {
+    let base = match env::var_os("XDG_CONFIG_HOME") {
+        Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ => PathBuf::from(env::var_os("HOME")?).join(".config"),
+    };
+    Some(base.join("ssh-keys").join("config.toml"))
+}
+
+/// Default values for top-level options, from a TOML file of `flag-name = value` lines such
+/// as `secret-id = "ssh-keys"` or `timeout = 60`.
+#[derive(Debug, Default)]
+pub struct Config {
+    settings: BTreeMap<String, String>,
+}
+
+impl Config {
+    /// Reads the config file at `path`. A missing file gives an empty config unless
+    /// `required` is set.
+    pub fn load(path: &Path, required: bool) -> Result<Self, anyhow::Error> {
+        let contents = match fs::read_to_string(path) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == io::ErrorKind::NotFound && !required => {
+                return Ok(Config::default())
+            }
+            Err(e) => return Err(e).with_context(|| format!("{}", path.display())),
+        };
+        let table = toml::from_str::<toml::value::Table>(&contents)
+            .with_context(|| format!("Invalid config file {}", path.display()))?;
+        let mut settings = BTreeMap::new();
+        for (key, value) in table {
+            if !SETTINGS.contains(&key.as_str()) {
+                anyhow::bail!(
+                    "Unknown setting {:?} in {}; expected one of {}",
+                    key,
+                    path.display(),
+                    SETTINGS.join(", ")
+                );
+            }
+            let value = match value {
+                toml::Value::String(s) => s,
+                toml::Value::Integer(i) => i.to_string(),
+                _ => anyhow::bail!(
+                    "Setting {:?} in {} must be a string or an integer",
+                    key,
+                    path.display()
+                ),
+            };
+            settings.insert(key, value);
+        }
+        Ok(Config { settings })
+    }
+
+    /// Forgets the value of `key`, so that the option's own default applies.
+    pub fn remove(&mut self, key: &str) {
+        self.settings.remove(key);
+    }
+
+    /// Command line arguments supplying the value of each setting whose option wasn't given
+    /// explicitly in `matches`, to be parsed ahead of the real arguments.
+    pub fn args(&self, matches: &ArgMatches<'_>) -> Vec<String> {
+        self.settings
+            .iter()
+            .filter(|(key, _)| matches.occurrences_of(key.as_str()) == 0)
+            .map(|(key, value)| format!("--{}={}", key, value))
+            .collect()
+    }
+}
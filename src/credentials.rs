@@ -0,0 +1,207 @@
+//! Selection of the AWS credentials provider.
+
+use std::env;
+use std::fmt;
+use std::str::FromStr;
+
+use async_trait::async_trait;
+use rusoto_core::{HttpClient, Region};
+use rusoto_credential::{
+    AutoRefreshingProvider, AwsCredentials, ChainProvider, CredentialsError, EnvironmentProvider,
+    ProfileProvider, ProvideAwsCredentials,
+};
+use rusoto_sts::{StsAssumeRoleSessionCredentialsProvider, StsClient};
+
+/// Profile used when none is given on the command line.
+pub const DEFAULT_PROFILE: &str = "bcmyers";
+
+/// Where to look for AWS credentials.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CredentialsSource {
+    /// Try the environment, then the profile, then the container and instance metadata
+    /// endpoints, as the AWS SDKs do
+    Chain,
+    /// Use the environment if `AWS_ACCESS_KEY_ID` is set and no profile was asked for, and
+    /// the profile otherwise
+    Auto,
+    /// Use `AWS_ACCESS_KEY_ID`, `AWS_SECRET_ACCESS_KEY` and `AWS_SESSION_TOKEN`
+    Env,
+    /// Use a named profile from ~/.aws/credentials
+    Profile,
+}
+
+impl FromStr for CredentialsSource {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "auto" => Ok(CredentialsSource::Auto),
+            "chain" => Ok(CredentialsSource::Chain),
+            "env" => Ok(CredentialsSource::Env),
+            "profile" => Ok(CredentialsSource::Profile),
+            _ => anyhow::bail!(
+                "Expected one of auto, chain, env or profile but got {:?}",
+                s
+            ),
+        }
+    }
+}
+
+/// The credentials provider chosen at startup.
+pub enum Credentials {
+    /// Credentials from the first source in the chain that has some
+    Chain {
+        /// Name of the profile tried after the environment
+        profile: String,
+        /// Provider caching the credentials found until they expire
+        provider: AutoRefreshingProvider<ChainProvider>,
+    },
+    /// Credentials from environment variables
+    Env(EnvironmentProvider),
+    /// Credentials from a named profile
+    Profile(ProfileProvider),
+    /// Temporary credentials for a role, assumed using other credentials
+    AssumeRole {
+        /// ARN of the assumed role
+        role_arn: String,
+        /// Description of the credentials used to assume the role
+        base: String,
+        /// Provider caching the temporary credentials until they expire
+        provider: AutoRefreshingProvider<StsAssumeRoleSessionCredentialsProvider>,
+    },
+}
+
+impl Credentials {
+    /// Builds the provider for `source`, using `profile` (or [`DEFAULT_PROFILE`]) when
+    /// credentials come from a profile.
+    pub fn new(source: CredentialsSource, profile: Option<String>) -> Result<Self, anyhow::Error> {
+        let use_env = match source {
+            CredentialsSource::Chain => {
+                let profile = profile.unwrap_or_else(|| DEFAULT_PROFILE.to_string());
+                let chain = ChainProvider::with_profile_provider(
+                    ProfileProvider::with_default_credentials(&profile)?,
+                );
+                return Ok(Credentials::Chain {
+                    profile,
+                    provider: AutoRefreshingProvider::new(chain)?,
+                });
+            }
+            CredentialsSource::Auto => {
+                profile.is_none() && env::var_os("AWS_ACCESS_KEY_ID").is_some()
+            }
+            CredentialsSource::Env => true,
+            CredentialsSource::Profile => false,
+        };
+        if use_env {
+            return Ok(Credentials::Env(EnvironmentProvider::default()));
+        }
+        let profile = profile.unwrap_or_else(|| DEFAULT_PROFILE.to_string());
+        Ok(Credentials::Profile(
+            ProfileProvider::with_default_credentials(profile)?,
+        ))
+    }
+
+    /// Wraps these credentials so that requests are made as the role `role_arn`, assumed
+    /// through STS in `region`. Roles requiring MFA need `mfa`, the serial number or ARN of
+    /// the MFA device and the code it currently shows.
+    pub fn assume_role(
+        self,
+        region: Region,
+        role_arn: String,
+        session_name: String,
+        external_id: Option<String>,
+        mfa: Option<(String, String)>,
+    ) -> Result<Self, anyhow::Error> {
+        validate_role_arn(&role_arn)?;
+        let base = self.to_string();
+        let sts = StsClient::new_with(HttpClient::new()?, self, region);
+        let (mfa_serial, mfa_code) = mfa.unzip();
+        let mut provider = StsAssumeRoleSessionCredentialsProvider::new(
+            sts,
+            role_arn.clone(),
+            session_name,
+            external_id,
+            None,
+            None,
+            mfa_serial,
+        );
+        if let Some(code) = mfa_code {
+            provider.set_mfa_code(code);
+        }
+        Ok(Credentials::AssumeRole {
+            role_arn,
+            base,
+            provider: AutoRefreshingProvider::new(provider)?,
+        })
+    }
+}
+
+/// Checks that `arn` looks like `arn:<partition>:iam::<account id>:role/<name>`.
+fn validate_role_arn(arn: &str) -> Result<(), anyhow::Error> {
+    let parts = arn.splitn(6, ':').collect::<Vec<_>>();
+    let valid = match parts.as_slice() {
+        ["arn", partition, "iam", "", account, resource] => {
+            partition.starts_with("aws")
+                && account.len() == 12
+                && account.bytes().all(|b| b.is_ascii_digit())
+                && resource
+                    .strip_prefix("role/")
+                    .is_some_and(|name| !name.is_empty())
+        }
+        _ => false,
+    };
+    if !valid {
+        anyhow::bail!(
+            "Invalid role ARN {:?}; expected something like arn:aws:iam::123456789012:role/name",
+            arn
+        );
+    }
+    Ok(())
+}
+
+impl fmt::Debug for Credentials {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Credentials::Chain { profile, .. } => {
+                f.debug_struct("Chain").field("profile", profile).finish()
+            }
+            Credentials::Env(p) => f.debug_tuple("Env").field(p).finish(),
+            Credentials::Profile(p) => f.debug_tuple("Profile").field(p).finish(),
+            Credentials::AssumeRole { role_arn, base, .. } => f
+                .debug_struct("AssumeRole")
+                .field("role_arn", role_arn)
+                .field("base", base)
+                .finish(),
+        }
+    }
+}
+
+impl fmt::Display for Credentials {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Credentials::Chain { profile, .. } => write!(
+                f,
+                "the default chain (environment variables, profile {}, container or \
+                 instance metadata)",
+                profile
+            ),
+            Credentials::Env(_) => write!(f, "environment variables"),
+            Credentials::Profile(p) => write!(f, "profile {}", p.profile()),
+            Credentials::AssumeRole { role_arn, base, .. } => {
+                write!(f, "role {} (assumed with {})", role_arn, base)
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl ProvideAwsCredentials for Credentials {
+    async fn credentials(&self) -> Result<AwsCredentials, CredentialsError> {
+        match self {
+            Credentials::Chain { provider, .. } => provider.credentials().await,
+            Credentials::Env(p) => p.credentials().await,
+            Credentials::Profile(p) => p.credentials().await,
+            Credentials::AssumeRole { provider, .. } => provider.credentials().await,
+        }
+    }
+}
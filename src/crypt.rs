@@ -0,0 +1,113 @@
+//! Client-side encryption of stored files with a passphrase, so that the secret only holds
+//! ciphertext even inside AWS.
+
+use age::secrecy::SecretString;
+use anyhow::Context as _;
+
+use crate::files::{Entry, Files};
+
+/// scrypt work factor (log2 of N) used when encrypting. Every file is encrypted on its own,
+/// so the default, which aims for a second per file, would make putting many keys slow.
+const WORK_FACTOR: u8 = 16;
+
+/// The passphrase given with `--passphrase`, or if it was given without a value, one typed on
+/// the terminal without echo. With `confirm` it has to be typed twice.
+pub(crate) fn read_passphrase(
+    value: Option<String>,
+    confirm: bool,
+) -> Result<SecretString, anyhow::Error> {
+    let passphrase = match value {
+        Some(passphrase) => passphrase,
+        None => {
+            let passphrase = rpassword::prompt_password("Passphrase: ")
+                .context("Failed to read the passphrase")?;
+            if confirm {
+                let again = rpassword::prompt_password("Passphrase again: ")
+                    .context("Failed to read the passphrase")?;
+                if again != passphrase {
+                    anyhow::bail!("The passphrases don't match");
+                }
+            }
+            passphrase
+        }
+    };
+    if passphrase.is_empty() {
+        anyhow::bail!("The passphrase is empty");
+    }
+    Ok(SecretString::from(passphrase))
+}
+
+/// Encrypts the contents of an entry with `passphrase`.
+pub(crate) fn encrypt_entry(
+    entry: &mut Entry,
+    passphrase: &SecretString,
+) -> Result<(), anyhow::Error> {
+    let mut recipient = age::scrypt::Recipient::new(passphrase.clone());
+    recipient.set_work_factor(WORK_FACTOR);
+    entry.contents = age::encrypt(&recipient, &entry.contents).context("Failed to encrypt")?;
+    entry.encrypted = true;
+    Ok(())
+}
+
+/// Decrypts the contents of an entry encrypted with [`encrypt_entry`].
+pub(crate) fn decrypt_entry(
+    entry: &mut Entry,
+    passphrase: &SecretString,
+) -> Result<(), anyhow::Error> {
+    let identity = age::scrypt::Identity::new(passphrase.clone());
+    entry.contents = age::decrypt(&identity, &entry.contents).context("Failed to decrypt")?;
+    entry.encrypted = false;
+    Ok(())
+}
+
+/// Decrypts every encrypted entry, failing if there are any and no passphrase was given.
+pub(crate) fn decrypt_files(
+    files: &mut Files,
+    passphrase: Option<&SecretString>,
+) -> Result<(), anyhow::Error> {
+    let mut entries = files
+        .iter_mut()
+        .filter(|(_, v)| v.encrypted)
+        .collect::<Vec<_>>();
+    entries.sort_by(|a, b| a.0.cmp(b.0));
+    for (k, v) in entries {
+        let passphrase = passphrase
+            .with_context(|| format!("Key {} is encrypted; pass --passphrase to decrypt it", k))?;
+        decrypt_entry(v, passphrase).with_context(|| {
+            format!("Key {} could not be decrypted; is the passphrase right?", k)
+        })?;
+    }
+    Ok(())
+}
+
+/// Encrypts the files about to be stored, given the files `stored` before. A file stored
+/// encrypted and unchanged keeps its stored ciphertext, since encrypting it again would give
+/// different bytes and make it look changed.
+pub(crate) fn encrypt_files(
+    map: &mut Files,
+    stored: Option<&Files>,
+    passphrase: &SecretString,
+) -> Result<(), anyhow::Error> {
+    for (k, v) in map.iter_mut() {
+        if let Some(old) = stored
+            .and_then(|stored| stored.get(k))
+            .filter(|old| old.encrypted)
+        {
+            let mut decrypted = old.clone();
+            match decrypt_entry(&mut decrypted, passphrase) {
+                Ok(()) if decrypted == *v => {
+                    *v = old.clone();
+                    continue;
+                }
+                Ok(()) => {}
+                Err(_) => log::warn!(
+                    "Warning: {} is stored encrypted with a different passphrase; it will be \
+                     encrypted with the new one",
+                    k
+                ),
+            }
+        }
+        encrypt_entry(v, passphrase).with_context(|| format!("Failed to encrypt {}", k))?;
+    }
+    Ok(())
+}
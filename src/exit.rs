@@ -0,0 +1,150 @@
+//! Classification of failures into the exit codes scripts can branch on.
+
+use std::fmt;
+use std::io;
+
+use rusoto_core::RusotoError;
+use rusoto_credential::CredentialsError;
+use rusoto_secretsmanager::{
+    CreateSecretError, DescribeSecretError, GetSecretValueError, ListSecretVersionIdsError,
+    ListSecretsError, PutSecretValueError, ReplicateSecretToRegionsError, TagResourceError,
+    UpdateSecretError, UpdateSecretVersionStageError,
+};
+
+use serde_json::json;
+
+use crate::backend::{is_access_denied, is_not_found};
+
+/// Kinds of failure that exit with their own code. Anything else exits with 1.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Failure {
+    /// The command line was invalid (exit code 2)
+    Usage,
+    /// Credentials couldn't be found, or weren't allowed to make a request (exit code 3)
+    Auth,
+    /// The secret doesn't exist (exit code 4)
+    NotFound,
+    /// Something to be created already exists, such as a file written by get (exit code 5)
+    Conflict,
+    /// A request couldn't be sent or timed out (exit code 6)
+    Network,
+}
+
+impl Failure {
+    /// The code the process exits with.
+    pub fn exit_code(self) -> i32 {
+        match self {
+            Failure::Usage => 2,
+            Failure::Auth => 3,
+            Failure::NotFound => 4,
+            Failure::Conflict => 5,
+            Failure::Network => 6,
+        }
+    }
+
+    /// The name given as the kind of the error in JSON output.
+    pub fn kind(self) -> &'static str {
+        match self {
+            Failure::Usage => "usage",
+            Failure::Auth => "auth",
+            Failure::NotFound => "not-found",
+            Failure::Conflict => "conflict",
+            Failure::Network => "network",
+        }
+    }
+
+    /// Wraps `message` in an error that is classified as this kind of failure.
+    pub fn error(self, message: String) -> anyhow::Error {
+        Classified {
+            failure: self,
+            message,
+        }
+        .into()
+    }
+}
+
+/// An error that can't be classified from its cause alone.
+#[derive(Debug)]
+struct Classified {
+    failure: Failure,
+    message: String,
+}
+
+impl fmt::Display for Classified {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl std::error::Error for Classified {}
+
+/// The kind of failure `e` is, if it is one with its own exit code.
+pub fn classify(e: &anyhow::Error) -> Option<Failure> {
+    if let Some(classified) = e.downcast_ref::<Classified>() {
+        return Some(classified.failure);
+    }
+    if is_not_found(e) {
+        return Some(Failure::NotFound);
+    }
+    if is_access_denied(e) || e.downcast_ref::<CredentialsError>().is_some() {
+        return Some(Failure::Auth);
+    }
+    if e.chain()
+        .filter_map(|cause| cause.downcast_ref::<io::Error>())
+        .any(|cause| cause.kind() == io::ErrorKind::AlreadyExists)
+    {
+        return Some(Failure::Conflict);
+    }
+    if matches!(
+        e.downcast_ref::<RusotoError<CreateSecretError>>(),
+        Some(RusotoError::Service(CreateSecretError::ResourceExists(_)))
+    ) {
+        return Some(Failure::Conflict);
+    }
+    classify_rusoto::<CreateSecretError>(e)
+        .or_else(|| classify_rusoto::<DescribeSecretError>(e))
+        .or_else(|| classify_rusoto::<GetSecretValueError>(e))
+        .or_else(|| classify_rusoto::<ListSecretVersionIdsError>(e))
+        .or_else(|| classify_rusoto::<ListSecretsError>(e))
+        .or_else(|| classify_rusoto::<PutSecretValueError>(e))
+        .or_else(|| classify_rusoto::<ReplicateSecretToRegionsError>(e))
+        .or_else(|| classify_rusoto::<TagResourceError>(e))
+        .or_else(|| classify_rusoto::<UpdateSecretError>(e))
+        .or_else(|| classify_rusoto::<UpdateSecretVersionStageError>(e))
+}
+
+/// Describes `e` for `--output json` as
+/// `{"error": "...", "kind": "...", "source": "..."}`: its message, the kind of failure it
+/// is (`other` if it has no exit code of its own) and the chain of errors that caused it,
+/// or null if there are none.
+pub fn error_json(e: &anyhow::Error) -> serde_json::Value {
+    // A rusoto error and the error it wraps have the same message.
+    let mut causes = e.chain().map(ToString::to_string).collect::<Vec<_>>();
+    causes.dedup();
+    let message = causes.remove(0);
+    json!({
+        "error": message,
+        "kind": classify(e).map_or("other", Failure::kind),
+        "source": if causes.is_empty() { None } else { Some(causes.join(": ")) },
+    })
+}
+
+/// Classifies the failures every AWS call can have in common.
+fn classify_rusoto<E>(e: &anyhow::Error) -> Option<Failure>
+where
+    E: std::error::Error + Send + Sync + 'static,
+{
+    match e.downcast_ref::<RusotoError<E>>()? {
+        RusotoError::Credentials(_) => Some(Failure::Auth),
+        RusotoError::HttpDispatch(_) => Some(Failure::Network),
+        // Rejected credentials aren't modelled errors, so they show up as unknown responses.
+        RusotoError::Unknown(response)
+            if ["UnrecognizedClientException", "ExpiredTokenException"]
+                .iter()
+                .any(|code| response.body_as_str().contains(code)) =>
+        {
+            Some(Failure::Auth)
+        }
+        _ => None,
+    }
+}
@@ -0,0 +1,174 @@
+//! Translation of common AWS errors into actionable messages.
+
+use std::fmt;
+
+use async_trait::async_trait;
+use rusoto_core::Region;
+use rusoto_secretsmanager::{
+    CreateSecretRequest, CreateSecretResponse, DescribeSecretRequest, DescribeSecretResponse,
+    GetSecretValueRequest, GetSecretValueResponse, ListSecretVersionIdsRequest,
+    ListSecretVersionIdsResponse, ListSecretsRequest, ListSecretsResponse, PutSecretValueRequest,
+    PutSecretValueResponse, ReplicateSecretToRegionsRequest, ReplicateSecretToRegionsResponse,
+    TagResourceRequest, UpdateSecretRequest, UpdateSecretResponse, UpdateSecretVersionStageRequest,
+    UpdateSecretVersionStageResponse,
+};
+
+use crate::backend::{is_access_denied, is_not_found, SecretsBackend};
+
+/// A backend that explains errors caused by missing permissions or a missing secret in terms
+/// of the credentials and region in use.
+///
+/// The original error is kept as the source of the explained one.
+#[derive(Debug)]
+pub struct Explain<B> {
+    inner: B,
+    credentials: String,
+    region: String,
+}
+
+impl<B: SecretsBackend> Explain<B> {
+    /// Wraps `inner`, which makes requests in `region` using `credentials`.
+    pub fn new(inner: B, credentials: &dyn fmt::Display, region: &Region) -> Self {
+        Explain {
+            inner,
+            credentials: credentials.to_string(),
+            region: region.name().to_string(),
+        }
+    }
+
+    /// Adds an explanation to `e` if it is one of the errors this knows about.
+    fn explain(&self, secret_id: &str, e: anyhow::Error) -> anyhow::Error {
+        if is_access_denied(&e) {
+            let message = format!(
+                "Access denied to secret {} with {}; check your IAM permissions",
+                secret_id, self.credentials
+            );
+            e.context(message)
+        } else if is_not_found(&e) {
+            let message = format!("Secret {} not found in region {}", secret_id, self.region);
+            e.context(message)
+        } else {
+            e
+        }
+    }
+}
+
+#[async_trait]
+impl<B: SecretsBackend> SecretsBackend for Explain<B> {
+    async fn get_secret_value(
+        &self,
+        request: GetSecretValueRequest,
+    ) -> Result<GetSecretValueResponse, anyhow::Error> {
+        let secret_id = request.secret_id.clone();
+        self.inner
+            .get_secret_value(request)
+            .await
+            .map_err(|e| self.explain(&secret_id, e))
+    }
+
+    async fn put_secret_value(
+        &self,
+        request: PutSecretValueRequest,
+    ) -> Result<PutSecretValueResponse, anyhow::Error> {
+        let secret_id = request.secret_id.clone();
+        self.inner
+            .put_secret_value(request)
+            .await
+            .map_err(|e| self.explain(&secret_id, e))
+    }
+
+    async fn list_secret_version_ids(
+        &self,
+        request: ListSecretVersionIdsRequest,
+    ) -> Result<ListSecretVersionIdsResponse, anyhow::Error> {
+        let secret_id = request.secret_id.clone();
+        self.inner
+            .list_secret_version_ids(request)
+            .await
+            .map_err(|e| self.explain(&secret_id, e))
+    }
+
+    async fn create_secret(
+        &self,
+        request: CreateSecretRequest,
+    ) -> Result<CreateSecretResponse, anyhow::Error> {
+        let secret_id = request.name.clone();
+        self.inner
+            .create_secret(request)
+            .await
+            .map_err(|e| self.explain(&secret_id, e))
+    }
+
+    async fn update_secret(
+        &self,
+        request: UpdateSecretRequest,
+    ) -> Result<UpdateSecretResponse, anyhow::Error> {
+        let secret_id = request.secret_id.clone();
+        self.inner
+            .update_secret(request)
+            .await
+            .map_err(|e| self.explain(&secret_id, e))
+    }
+
+    async fn tag_resource(&self, request: TagResourceRequest) -> Result<(), anyhow::Error> {
+        let secret_id = request.secret_id.clone();
+        self.inner
+            .tag_resource(request)
+            .await
+            .map_err(|e| self.explain(&secret_id, e))
+    }
+
+    async fn update_secret_version_stage(
+        &self,
+        request: UpdateSecretVersionStageRequest,
+    ) -> Result<UpdateSecretVersionStageResponse, anyhow::Error> {
+        let secret_id = request.secret_id.clone();
+        self.inner
+            .update_secret_version_stage(request)
+            .await
+            .map_err(|e| self.explain(&secret_id, e))
+    }
+
+    async fn describe_secret(
+        &self,
+        request: DescribeSecretRequest,
+    ) -> Result<DescribeSecretResponse, anyhow::Error> {
+        let secret_id = request.secret_id.clone();
+        self.inner
+            .describe_secret(request)
+            .await
+            .map_err(|e| self.explain(&secret_id, e))
+    }
+
+    async fn replicate_secret_to_regions(
+        &self,
+        request: ReplicateSecretToRegionsRequest,
+    ) -> Result<ReplicateSecretToRegionsResponse, anyhow::Error> {
+        let secret_id = request.secret_id.clone();
+        self.inner
+            .replicate_secret_to_regions(request)
+            .await
+            .map_err(|e| self.explain(&secret_id, e))
+    }
+
+    async fn list_secrets(
+        &self,
+        request: ListSecretsRequest,
+    ) -> Result<ListSecretsResponse, anyhow::Error> {
+        self.inner.list_secrets(request).await.map_err(|e| {
+            if is_access_denied(&e) {
+                let message = format!(
+                    "Access denied to listing secrets with {}; check your IAM permissions",
+                    self.credentials
+                );
+                e.context(message)
+            } else {
+                e
+            }
+        })
+    }
+
+    fn size_limit(&self) -> usize {
+        self.inner.size_limit()
+    }
+}
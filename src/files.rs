@@ -0,0 +1,365 @@
+//! The stored representation of a set of files.
+
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::io::{Read as _, Write as _};
+
+use anyhow::Context as _;
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use serde::{Deserialize, Serialize};
+
+/// Map of file names to the files stored under them.
+pub type Files = HashMap<String, Entry>;
+
+/// Maximum size in bytes of a secret value accepted by AWS Secrets Manager.
+pub const SECRET_SIZE_LIMIT: usize = 65536;
+
+/// Prefix marking a `secret_binary` value as gzipped JSON.
+const GZIP_MAGIC: &[u8] = b"ssh-keys+gzip\n";
+
+/// Which field of the secret value the files are stored in, and how.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Storage {
+    /// Plain JSON in `secret_string`
+    String,
+    /// Plain JSON in `secret_binary`
+    Binary,
+    /// A magic prefix followed by gzipped JSON in `secret_binary`
+    Compressed,
+}
+
+/// How a secret value is serialized, so that a changed set of files can be written back the
+/// same way it was read.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Format {
+    /// Which field of the secret value the files are stored in, and how
+    pub storage: Storage,
+    /// Whether the JSON is written without whitespace
+    pub compact: bool,
+}
+
+impl Default for Format {
+    /// Pretty-printed JSON in `secret_string`, as written by a put without any options.
+    fn default() -> Self {
+        Format {
+            storage: Storage::String,
+            compact: false,
+        }
+    }
+}
+
+/// A serialized secret value, ready to be uploaded.
+#[derive(Clone, Debug)]
+pub enum Payload {
+    /// Plain JSON, stored in `secret_string`
+    Text(String),
+    /// Plain or gzipped JSON, stored in `secret_binary`
+    Binary(Vec<u8>),
+}
+
+impl Payload {
+    /// Size in bytes of the value as it will be stored.
+    pub(crate) fn len(&self) -> usize {
+        match self {
+            Payload::Text(s) => s.len(),
+            Payload::Binary(b) => b.len(),
+        }
+    }
+
+    /// Splits the value into the `secret_string` and `secret_binary` it is stored as.
+    pub(crate) fn into_parts(self) -> (Option<String>, Option<Vec<u8>>) {
+        match self {
+            Payload::Text(s) => (Some(s), None),
+            Payload::Binary(b) => (None, Some(b)),
+        }
+    }
+}
+
+/// A stored file: its raw contents plus the Unix permissions and modification time it had
+/// when it was put.
+///
+/// The secret is a JSON object mapping file names to entries. An entry is an object holding
+/// the contents, the octal mode and the modification time (in seconds since the Unix epoch)
+/// of the original file. Contents that are valid utf-8 are stored as plain strings; anything
+/// else is stored base64-encoded with a marker so that it round-trips byte for byte:
+///
+/// ```json
+/// {
+///   "id_ed25519.pub": {
+///     "contents": "ssh-ed25519 AAAA... user@host\n",
+///     "mode": "644",
+///     "mtime": 1622548800
+///   },
+///   "key.der": {
+///     "contents": "MIIEvQIBADANBgkqhkiG9w0BAQEFAASC...",
+///     "encoding": "base64",
+///     "mode": "600"
+///   }
+/// }
+/// ```
+///
+/// Public keys put with `--strip-comments` also hold the `comments` removed from their lines,
+/// as a list with one string (possibly empty) for each line.
+///
+/// Entries put with `--passphrase` have contents encrypted with [age](https://age-encryption.org)
+/// and are marked with `"encryption": "age"`; the base64-encoded contents are the ciphertext.
+///
+/// Entries written by older versions are plain strings holding only the contents; they are
+/// still accepted and get a mode derived from the file name. Entries without an mtime get
+/// whatever time they are written at.
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(try_from = "RawEntry", into = "RawEntry")]
+pub struct Entry {
+    /// Raw contents of the file
+    pub contents: Vec<u8>,
+    /// Unix permissions of the file, if known
+    pub mode: Option<u32>,
+    /// Modification time of the file in seconds since the Unix epoch, if known
+    pub mtime: Option<i64>,
+    /// Comments stripped from the lines of a public key, to be restored on request
+    pub comments: Option<Vec<String>>,
+    /// Whether the contents are encrypted with a passphrase, as by put --passphrase
+    pub encrypted: bool,
+}
+
+impl Entry {
+    /// Mode to create the file with, falling back to read-only permissions chosen from the
+    /// file name when the entry doesn't record one.
+    pub fn mode(&self, name: &str) -> u32 {
+        self.mode.unwrap_or_else(|| default_mode(name))
+    }
+}
+
+/// JSON representation of [`Entry`].
+#[derive(Deserialize, Serialize)]
+#[serde(untagged)]
+enum RawEntry {
+    Text(String),
+    Full {
+        contents: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        encoding: Option<Encoding>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        mode: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        mtime: Option<i64>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        comments: Option<Vec<String>>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        encryption: Option<Encryption>,
+    },
+}
+
+/// Encodings used for file contents that are not valid utf-8.
+#[derive(Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+enum Encoding {
+    Base64,
+}
+
+/// Ways file contents can be encrypted on the client before being stored.
+#[derive(Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+enum Encryption {
+    Age,
+}
+
+impl TryFrom<RawEntry> for Entry {
+    type Error = anyhow::Error;
+
+    fn try_from(raw: RawEntry) -> Result<Self, Self::Error> {
+        match raw {
+            RawEntry::Text(s) => Ok(Entry {
+                contents: s.into_bytes(),
+                mode: None,
+                mtime: None,
+                comments: None,
+                encrypted: false,
+            }),
+            RawEntry::Full {
+                contents,
+                encoding,
+                mode,
+                mtime,
+                comments,
+                encryption,
+            } => Ok(Entry {
+                contents: match encoding {
+                    None => contents.into_bytes(),
+                    Some(Encoding::Base64) => base64::decode(contents)?,
+                },
+                mode: mode
+                    .map(|m| u32::from_str_radix(&m, 8))
+                    .transpose()
+                    .context("Invalid octal file mode")?,
+                mtime,
+                comments,
+                encrypted: encryption.is_some(),
+            }),
+        }
+    }
+}
+
+impl From<Entry> for RawEntry {
+    fn from(entry: Entry) -> Self {
+        let (contents, encoding) = match String::from_utf8(entry.contents) {
+            Ok(s) => (s, None),
+            Err(e) => (base64::encode(e.into_bytes()), Some(Encoding::Base64)),
+        };
+        let encryption = if entry.encrypted {
+            Some(Encryption::Age)
+        } else {
+            None
+        };
+        match (
+            encoding,
+            entry.mode,
+            entry.mtime,
+            entry.comments,
+            encryption,
+        ) {
+            (None, None, None, None, None) => RawEntry::Text(contents),
+            (encoding, mode, mtime, comments, encryption) => RawEntry::Full {
+                contents,
+                encoding,
+                mode: mode.map(|m| format!("{:o}", m)),
+                mtime,
+                comments,
+                encryption,
+            },
+        }
+    }
+}
+
+/// Mode for files whose original permissions are unknown: read-only, and only readable by the
+/// owner unless the name marks it as a public key.
+pub fn default_mode(name: &str) -> u32 {
+    if is_public_key_name(name) {
+        0o444
+    } else {
+        0o400
+    }
+}
+
+/// Whether the file name marks a public key.
+pub fn is_public_key_name(name: &str) -> bool {
+    name.ends_with(".pub") || name.ends_with(".public")
+}
+
+/// Whether the file name marks a key replaced by rotate, i.e. `name.old` or `name.old.pub`.
+pub(crate) fn is_retired_key_name(name: &str) -> bool {
+    name.ends_with(".old") || name.ends_with(".old.pub")
+}
+
+/// Checks that a key name is a relative path with `/` separators that stays inside the
+/// directory it is written into, e.g. `id_ed25519` or `config.d/host1` but not
+/// `../id_ed25519`, `/etc/passwd` or `..\id_ed25519`.
+pub fn validate_key_path(name: &str) -> Result<(), anyhow::Error> {
+    if name.starts_with('/') {
+        anyhow::bail!("Refusing to write key {:?}: it is an absolute path", name);
+    }
+    // Backslashes are ordinary file name characters here, but a key holding one was most
+    // likely meant as a Windows path, where it could escape the directory.
+    if name.contains('\\') {
+        anyhow::bail!(
+            "Refusing to write key {:?}: it contains a backslash; use / to separate directories",
+            name
+        );
+    }
+    if name
+        .split('/')
+        .any(|part| part.is_empty() || part == "." || part == "..")
+    {
+        anyhow::bail!(
+            "Refusing to write key {:?}: it could be written outside the output directory",
+            name
+        );
+    }
+    Ok(())
+}
+
+/// Parses a secret value written by [`encode`], which is either JSON in `secret_string` or
+/// plain or gzipped JSON in `secret_binary`.
+pub fn decode(string: Option<String>, binary: Option<&[u8]>) -> Result<Files, anyhow::Error> {
+    decode_format(string, binary).map(|(files, _)| files)
+}
+
+/// Parses a secret value like [`decode`], also returning the format it was written in.
+pub fn decode_format(
+    string: Option<String>,
+    binary: Option<&[u8]>,
+) -> Result<(Files, Format), anyhow::Error> {
+    // Pretty-printed JSON always has a line break, and compact JSON never does, since line
+    // breaks inside strings are escaped.
+    let format = |storage, json: &[u8]| Format {
+        storage,
+        compact: !json.contains(&b'\n'),
+    };
+    if let Some(s) = string {
+        let files = serde_json::from_str::<Files>(&s)?;
+        return Ok((files, format(Storage::String, s.as_bytes())));
+    }
+    let binary = binary.ok_or_else(|| {
+        anyhow::anyhow!("Expected secret_string or secret_binary in response but did not get one")
+    })?;
+    let compressed = match binary.strip_prefix(GZIP_MAGIC) {
+        Some(compressed) => compressed,
+        None => {
+            let files = serde_json::from_slice::<Files>(binary)
+                .context("secret_binary is neither JSON nor gzipped JSON written by ssh-keys")?;
+            return Ok((files, format(Storage::Binary, binary)));
+        }
+    };
+    let mut json = Vec::new();
+    GzDecoder::new(compressed)
+        .read_to_end(&mut json)
+        .context("Failed to decompress secret_binary")?;
+    let files = serde_json::from_slice::<Files>(&json)?;
+    Ok((files, format(Storage::Compressed, &json)))
+}
+
+/// Serializes the map of file names to file entries for the given `storage`, without any
+/// whitespace if `compact` is set, and fails if the result is larger than `limit` bytes.
+pub fn encode(
+    files: &Files,
+    storage: Storage,
+    compact: bool,
+    limit: usize,
+) -> Result<Payload, anyhow::Error> {
+    let s = if compact {
+        serde_json::to_string(files)?
+    } else {
+        serde_json::to_string_pretty(files)?
+    };
+    let payload = match storage {
+        Storage::String => Payload::Text(s),
+        Storage::Binary => Payload::Binary(s.into_bytes()),
+        Storage::Compressed => {
+            let mut encoder = GzEncoder::new(GZIP_MAGIC.to_vec(), Compression::best());
+            encoder.write_all(s.as_bytes())?;
+            Payload::Binary(encoder.finish()?)
+        }
+    };
+    if payload.len() > limit {
+        let mut sizes = files
+            .iter()
+            .map(|(k, v)| (v.contents.len(), k.as_str()))
+            .collect::<Vec<_>>();
+        sizes.sort_unstable_by(|a, b| b.cmp(a));
+        let largest = sizes
+            .iter()
+            .take(5)
+            .map(|(size, k)| format!("  - {} ({} bytes)", k, size))
+            .collect::<Vec<_>>()
+            .join("\n");
+        anyhow::bail!(
+            "Serialized secret is {} bytes, which exceeds the limit of {} bytes. Largest keys:\n{}",
+            payload.len(),
+            limit,
+            largest
+        );
+    }
+    Ok(payload)
+}
@@ -0,0 +1,378 @@
+//! Parsing and sanity checks for ssh key files.
+
+use std::convert::{TryFrom as _, TryInto as _};
+use std::str::FromStr;
+
+use aes::{Aes128, Aes192, Aes256};
+use anyhow::Context as _;
+use block_modes::block_padding::Pkcs7;
+use block_modes::{BlockMode as _, Cbc};
+use des::TdesEde3;
+use md5::Md5;
+use rand::rngs::OsRng;
+use sha2::{Digest as _, Sha256};
+use ssh_encoding::Encode as _;
+use ssh_key::public::{KeyData, RsaPublicKey};
+use ssh_key::{Algorithm, LineEnding, Mpint, PrivateKey};
+
+use crate::files::is_public_key_name;
+
+/// A public key line in authorized_keys format.
+#[derive(Debug)]
+pub struct PublicKey {
+    blob: Vec<u8>,
+}
+
+impl PublicKey {
+    /// SHA256 fingerprint in the format printed by `ssh-keygen -l`.
+    pub fn fingerprint(&self) -> String {
+        let digest = Sha256::digest(&self.blob);
+        format!(
+            "SHA256:{}",
+            base64::encode_config(digest, base64::STANDARD_NO_PAD)
+        )
+    }
+}
+
+/// Kind of key pair to generate.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum KeyType {
+    /// An Ed25519 key
+    Ed25519,
+    /// A 4096 bit RSA key
+    Rsa,
+}
+
+impl FromStr for KeyType {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "ed25519" => Ok(KeyType::Ed25519),
+            "rsa" => Ok(KeyType::Rsa),
+            _ => anyhow::bail!("Expected one of ed25519 or rsa but got {:?}", s),
+        }
+    }
+}
+
+/// Generates a new unencrypted key pair, returning the private key in OpenSSH format and the
+/// public key line, as `ssh-keygen` would write them to `id_*` and `id_*.pub`.
+pub fn generate_key_pair(
+    key_type: KeyType,
+    comment: &str,
+) -> Result<(String, String), anyhow::Error> {
+    let algorithm = match key_type {
+        KeyType::Ed25519 => Algorithm::Ed25519,
+        KeyType::Rsa => Algorithm::Rsa { hash: None },
+    };
+    let mut private =
+        PrivateKey::random(&mut OsRng, algorithm).context("Failed to generate key")?;
+    private.set_comment(comment);
+    let public = private
+        .public_key()
+        .to_openssh()
+        .context("Failed to encode public key")?;
+    let private = private
+        .to_openssh(LineEnding::LF)
+        .context("Failed to encode private key")?;
+    Ok((private.to_string(), format!("{}\n", public)))
+}
+
+/// Whether a public key file holds the public half of a private key file.
+///
+/// The public key is read from the private key file without decrypting it where the format
+/// allows: OpenSSH private keys carry it in the clear, and PEM RSA keys contain its numbers,
+/// which for an encrypted key takes its `passphrase`. Returns `None` when the private key
+/// can't be read, e.g. an encrypted PEM key without the right passphrase.
+pub fn key_pair_matches(private: &[u8], public: &[u8], passphrase: Option<&str>) -> Option<bool> {
+    let derived = derive_public_key(&read_pem(private)?, passphrase)?;
+    let public = std::str::from_utf8(public)
+        .ok()?
+        .lines()
+        .map(str::trim)
+        .find(|line| !line.is_empty() && !line.starts_with('#'))?;
+    Some(parse_public_key(public).ok()?.blob == derived)
+}
+
+/// Parses a private key in OpenSSH format, the only one that can be handed to an ssh-agent.
+/// Older PEM keys have to be converted first.
+pub(crate) fn parse_private_key(contents: &[u8]) -> Result<PrivateKey, anyhow::Error> {
+    match read_pem(contents) {
+        Some(pem) if pem.label == "OPENSSH " => {}
+        Some(_) => anyhow::bail!(
+            "Private key is in PEM format; convert it to OpenSSH format with ssh-keygen -p"
+        ),
+        None => anyhow::bail!("Not a private key"),
+    }
+    let s = std::str::from_utf8(contents).context("Private key is not valid utf-8")?;
+    PrivateKey::from_openssh(s).context("Failed to parse private key")
+}
+
+/// Encodes a decrypted private key the way ssh-agent expects it: the key type followed by
+/// the fields of the key.
+pub(crate) fn encode_agent_key(key: &PrivateKey) -> Result<Vec<u8>, anyhow::Error> {
+    let mut encoded = Vec::new();
+    key.key_data()
+        .encode(&mut encoded)
+        .context("Failed to encode private key")?;
+    Ok(encoded)
+}
+
+/// Whether a private key is a PEM key encrypted with a passphrase, which is needed to check
+/// it with [`key_pair_matches`].
+pub fn is_encrypted_pem_key(private: &[u8]) -> bool {
+    read_pem(private).is_some_and(|pem| pem.encryption.is_some())
+}
+
+/// The parts of a PEM armored private key.
+struct Pem {
+    /// Text between `BEGIN ` and `PRIVATE KEY`, e.g. `RSA ` or `OPENSSH `
+    label: String,
+    /// Cipher and IV from the `DEK-Info` header of an encrypted key
+    encryption: Option<(String, Vec<u8>)>,
+    /// Decoded body, still encrypted if `encryption` is set
+    body: Vec<u8>,
+}
+
+/// Splits a PEM armored private key into its label, headers and body.
+fn read_pem(private: &[u8]) -> Option<Pem> {
+    let s = std::str::from_utf8(private).ok()?;
+    let mut lines = s.lines().map(str::trim).filter(|line| !line.is_empty());
+    let label = lines
+        .next()?
+        .strip_prefix("-----BEGIN ")?
+        .strip_suffix("PRIVATE KEY-----")?
+        .to_string();
+    let mut encryption = None;
+    let mut body = String::new();
+    for line in lines.take_while(|line| !line.starts_with("-----END ")) {
+        match line.split_once(':') {
+            Some(("DEK-Info", value)) => {
+                let (cipher, iv) = value.trim().split_once(',')?;
+                encryption = Some((cipher.to_string(), decode_hex(iv)?));
+            }
+            Some(_) => {}
+            None => body.push_str(line),
+        }
+    }
+    Some(Pem {
+        label,
+        encryption,
+        body: base64::decode(body).ok()?,
+    })
+}
+
+/// Extracts the public key blob from a private key, decrypting PEM keys with `passphrase`.
+fn derive_public_key(pem: &Pem, passphrase: Option<&str>) -> Option<Vec<u8>> {
+    let der = match &pem.encryption {
+        Some((cipher, iv)) => decrypt_pem(cipher, iv, passphrase?.as_bytes(), &pem.body)?,
+        None => pem.body.clone(),
+    };
+    match pem.label.as_str() {
+        "OPENSSH " => openssh_public_key(&der),
+        "RSA " => rsa_public_key(&der),
+        _ => None,
+    }
+}
+
+/// Decrypts the body of a PEM key encrypted the way OpenSSL does, with a key derived from the
+/// passphrase and the start of the IV by `EVP_BytesToKey` with MD5.
+fn decrypt_pem(cipher: &str, iv: &[u8], passphrase: &[u8], body: &[u8]) -> Option<Vec<u8>> {
+    let key_len = match cipher {
+        "AES-128-CBC" => 16,
+        "AES-192-CBC" => 24,
+        "AES-256-CBC" => 32,
+        "DES-EDE3-CBC" => 24,
+        _ => return None,
+    };
+    let salt = iv.get(..8)?;
+    let mut key = Vec::with_capacity(key_len + 16);
+    let mut block = Vec::new();
+    while key.len() < key_len {
+        let mut md5 = Md5::new();
+        md5.update(&block);
+        md5.update(passphrase);
+        md5.update(salt);
+        block = md5.finalize().to_vec();
+        key.extend_from_slice(&block);
+    }
+    key.truncate(key_len);
+    match cipher {
+        "AES-128-CBC" => Cbc::<Aes128, Pkcs7>::new_from_slices(&key, iv)
+            .ok()?
+            .decrypt_vec(body)
+            .ok(),
+        "AES-192-CBC" => Cbc::<Aes192, Pkcs7>::new_from_slices(&key, iv)
+            .ok()?
+            .decrypt_vec(body)
+            .ok(),
+        "AES-256-CBC" => Cbc::<Aes256, Pkcs7>::new_from_slices(&key, iv)
+            .ok()?
+            .decrypt_vec(body)
+            .ok(),
+        _ => Cbc::<TdesEde3, Pkcs7>::new_from_slices(&key, iv)
+            .ok()?
+            .decrypt_vec(body)
+            .ok(),
+    }
+}
+
+/// Decodes a string of hex digits.
+fn decode_hex(s: &str) -> Option<Vec<u8>> {
+    (0..s.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(s.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+/// Reads the public key stored unencrypted at the start of an `openssh-key-v1` private key.
+fn openssh_public_key(data: &[u8]) -> Option<Vec<u8>> {
+    let mut rest = data.strip_prefix(b"openssh-key-v1\0".as_ref())?;
+    // Cipher name, KDF name and KDF options come before the number of keys.
+    for _ in 0..3 {
+        ssh_string(&mut rest)?;
+    }
+    let count = rest.get(..4)?;
+    rest = &rest[4..];
+    if count == [0, 0, 0, 0] {
+        return None;
+    }
+    Some(ssh_string(&mut rest)?.to_vec())
+}
+
+/// Reads a length-prefixed string from the start of `data`.
+fn ssh_string<'a>(data: &mut &'a [u8]) -> Option<&'a [u8]> {
+    let len = u32::from_be_bytes(data.get(..4)?.try_into().ok()?) as usize;
+    let value = data.get(4..4 + len)?;
+    *data = &data[4 + len..];
+    Some(value)
+}
+
+/// Builds the `ssh-rsa` public key blob from the modulus and public exponent of a DER
+/// encoded PKCS#1 `RSAPrivateKey`.
+fn rsa_public_key(der: &[u8]) -> Option<Vec<u8>> {
+    let key = pkcs1::RsaPrivateKey::try_from(der).ok()?;
+    let public = KeyData::Rsa(RsaPublicKey {
+        e: Mpint::from_positive_bytes(key.public_exponent.as_bytes()).ok()?,
+        n: Mpint::from_positive_bytes(key.modulus.as_bytes()).ok()?,
+    });
+    let mut blob = Vec::new();
+    public.encode(&mut blob).ok()?;
+    Some(blob)
+}
+
+/// Whether `key_type` names a kind of public key, e.g. `ssh-ed25519`.
+fn is_public_key_type(key_type: &str) -> bool {
+    key_type.starts_with("ssh-")
+        || key_type.starts_with("ecdsa-sha2-")
+        || key_type.starts_with("sk-")
+}
+
+/// Parses a `type base64 [comment]` public key line.
+pub fn parse_public_key(line: &str) -> Result<PublicKey, anyhow::Error> {
+    let mut fields = line.split_whitespace();
+    let key_type = fields.next().unwrap_or_default();
+    if !is_public_key_type(key_type) {
+        anyhow::bail!("Unrecognized public key type {:?}", key_type);
+    }
+    let blob = fields
+        .next()
+        .ok_or_else(|| anyhow::anyhow!("Public key is missing its base64 data"))?;
+    let blob = base64::decode(blob).context("Public key data is not valid base64")?;
+    Ok(PublicKey { blob })
+}
+
+/// Splits a `type base64 [comment]` line after the base64 data, so that the second half is the
+/// comment with the whitespace before it. Returns `None` for lines that aren't public keys.
+fn split_comment(line: &str) -> Option<(&str, &str)> {
+    let type_start = line.len() - line.trim_start().len();
+    let type_end = type_start + line[type_start..].find(char::is_whitespace)?;
+    if !is_public_key_type(&line[type_start..type_end]) {
+        return None;
+    }
+    let blob_start = line.len() - line[type_end..].trim_start().len();
+    let blob_end = line[blob_start..]
+        .find(char::is_whitespace)
+        .map_or(line.len(), |i| blob_start + i);
+    Some(line.split_at(blob_end))
+}
+
+/// Removes the comment from each public key line of `contents`, leaving `type base64`. Returns
+/// the stripped contents and what was removed from each line, which is empty for lines
+/// without a comment, so that [`restore_comments`] can put everything back byte for byte.
+pub fn strip_comments(contents: &str) -> (String, Vec<String>) {
+    let mut stripped = String::with_capacity(contents.len());
+    let mut comments = Vec::new();
+    for line in contents.split_inclusive('\n') {
+        let (text, newline) = match line.strip_suffix('\n') {
+            Some(text) => (text, "\n"),
+            None => (line, ""),
+        };
+        let (key, comment) = split_comment(text).unwrap_or((text, ""));
+        stripped.push_str(key);
+        stripped.push_str(newline);
+        comments.push(comment.to_string());
+    }
+    (stripped, comments)
+}
+
+/// Appends the comments removed by [`strip_comments`] back onto the lines of `contents`.
+pub fn restore_comments(contents: &str, comments: &[String]) -> String {
+    let mut restored = String::with_capacity(contents.len());
+    let mut comments = comments.iter();
+    for line in contents.split_inclusive('\n') {
+        let (text, newline) = match line.strip_suffix('\n') {
+            Some(text) => (text, "\n"),
+            None => (line, ""),
+        };
+        restored.push_str(text);
+        restored.push_str(comments.next().map_or("", String::as_str));
+        restored.push_str(newline);
+    }
+    restored
+}
+
+/// Checks that a file about to be stored looks like a well-formed ssh key.
+///
+/// Public keys (by file name) must be in authorized_keys format, i.e. `type base64 [comment]`
+/// on each line. Files named like private keys (`id_*`) or containing private key armor must
+/// have matching `-----BEGIN ... PRIVATE KEY-----` / `-----END ... PRIVATE KEY-----` lines.
+/// Anything else, e.g. `config` or `known_hosts`, is not checked.
+pub fn validate_key(name: &str, contents: &[u8]) -> Result<(), anyhow::Error> {
+    if is_public_key_name(name) {
+        let s = std::str::from_utf8(contents).context("Public key is not valid utf-8")?;
+        let mut found = false;
+        for line in s.lines().map(str::trim) {
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            parse_public_key(line)?;
+            found = true;
+        }
+        if !found {
+            anyhow::bail!("File does not contain a public key");
+        }
+        return Ok(());
+    }
+    let s = match std::str::from_utf8(contents) {
+        Ok(s) => s,
+        Err(_) if name.starts_with("id_") => anyhow::bail!("Private key is not valid utf-8"),
+        Err(_) => return Ok(()),
+    };
+    if !name.starts_with("id_") && !s.contains("PRIVATE KEY-----") {
+        return Ok(());
+    }
+    let mut lines = s.lines().map(str::trim).filter(|line| !line.is_empty());
+    let label = lines
+        .next()
+        .and_then(|line| line.strip_prefix("-----BEGIN "))
+        .and_then(|line| line.strip_suffix("PRIVATE KEY-----"))
+        .ok_or_else(|| {
+            anyhow::anyhow!("Private key does not start with a BEGIN PRIVATE KEY line")
+        })?;
+    let end = format!("-----END {}PRIVATE KEY-----", label);
+    if lines.next_back() != Some(end.as_str()) {
+        anyhow::bail!("Private key does not end with {:?}; is it truncated?", end);
+    }
+    Ok(())
+}
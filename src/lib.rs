@@ -0,0 +1,58 @@
+//! ssh-keys
+//!
+//! Gets and puts a directory of ssh keys to a single secret, stored as a JSON map of file
+//! names to file contents. The `ssh-keys` binary is a thin command line wrapper around the
+//! commands exported here, which work against any [`SecretsBackend`].
+
+#![cfg(unix)]
+#![deny(clippy::all)]
+#![deny(dead_code)]
+#![deny(missing_debug_implementations)]
+#![deny(missing_docs)]
+#![deny(rust_2018_idioms)]
+#![deny(unsafe_code)]
+#![deny(unused_imports)]
+
+mod agent;
+mod aws_config;
+mod backend;
+mod cache;
+mod commands;
+mod config;
+mod credentials;
+mod crypt;
+mod exit;
+mod explain;
+mod files;
+mod keys;
+mod local;
+mod output;
+mod retry;
+mod scan;
+mod ssm;
+mod timeout;
+
+pub use crate::aws_config::{
+    default_aws_config_path, default_aws_credentials_path, profile_names, profile_region, Profile,
+};
+pub use crate::backend::{BackendKind, SecretsBackend};
+pub use crate::cache::{default_cache_dir, Cache};
+pub use crate::commands::*;
+pub use crate::config::{default_config_path, Config};
+pub use crate::credentials::{Credentials, CredentialsSource, DEFAULT_PROFILE};
+pub use crate::exit::{classify, error_json, Failure};
+pub use crate::explain::Explain;
+pub use crate::files::{
+    decode, decode_format, default_mode, encode, is_public_key_name, validate_key_path, Entry,
+    Files, Format, Payload, Storage, SECRET_SIZE_LIMIT,
+};
+pub use crate::keys::{
+    generate_key_pair, is_encrypted_pem_key, key_pair_matches, parse_public_key, restore_comments,
+    strip_comments, validate_key, KeyType, PublicKey,
+};
+pub use crate::local::LocalBackend;
+pub use crate::output::{ColorChoice, OutputFormat};
+pub use crate::retry::Retry;
+pub use crate::scan::{scan, scan_tar, ScanOpt};
+pub use crate::ssm::SsmBackend;
+pub use crate::timeout::Timeout;
@@ -0,0 +1,276 @@
+//! A JSON file on local disk as a place to keep the secret.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::{self, Write as _};
+use std::os::unix::fs::OpenOptionsExt as _;
+use std::path::{Path, PathBuf};
+
+use anyhow::Context as _;
+use async_trait::async_trait;
+use rusoto_core::RusotoError;
+use rusoto_secretsmanager::{
+    CreateSecretError, CreateSecretRequest, CreateSecretResponse, DescribeSecretError,
+    DescribeSecretRequest, DescribeSecretResponse, GetSecretValueError, GetSecretValueRequest,
+    GetSecretValueResponse, ListSecretsRequest, ListSecretsResponse, PutSecretValueError,
+    PutSecretValueRequest, PutSecretValueResponse, SecretListEntry, Tag, TagResourceError,
+    TagResourceRequest,
+};
+use uuid::Uuid;
+
+use crate::backend::{is_current_stage, SecretsBackend};
+
+/// Stores each secret as `<secret id>.json` in a local directory, without encryption.
+///
+/// The file holds exactly what Secrets Manager would, so it can be moved to or from the AWS
+/// backends. Only the current version is kept, with the secret's tags in the hidden file
+/// `.<secret id>.tags.json` beside it. This is meant for development, tests and
+/// demos rather than for keeping real keys.
+#[derive(Debug)]
+pub struct LocalBackend {
+    dir: PathBuf,
+}
+
+impl LocalBackend {
+    /// Keeps secrets in `dir`.
+    pub fn new(dir: PathBuf) -> Self {
+        LocalBackend { dir }
+    }
+
+    /// Path of the file holding a secret.
+    fn path(&self, secret_id: &str) -> PathBuf {
+        self.dir.join(format!("{}.json", secret_id))
+    }
+
+    /// Path of the file holding a secret's tags.
+    fn tags_path(&self, secret_id: &str) -> PathBuf {
+        self.dir.join(format!(".{}.tags.json", secret_id))
+    }
+
+    /// Reads the tags of a secret, which has none until it is first tagged.
+    fn read_tags(&self, secret_id: &str) -> Result<BTreeMap<String, String>, anyhow::Error> {
+        let path = self.tags_path(secret_id);
+        match fs::read(&path) {
+            Ok(contents) => {
+                serde_json::from_slice(&contents).with_context(|| format!("{}", path.display()))
+            }
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(BTreeMap::new()),
+            Err(e) => Err(e).with_context(|| format!("{}", path.display())),
+        }
+    }
+
+    /// Replaces the file holding a secret with `contents` in one step, so that readers never
+    /// see a partially written secret.
+    fn write(&self, secret_id: &str, contents: &[u8]) -> Result<(), anyhow::Error> {
+        self.replace(&self.path(secret_id), contents)
+    }
+
+    /// Replaces the file at `path`, in the secrets directory, with `contents` in one step.
+    fn replace(&self, path: &Path, contents: &[u8]) -> Result<(), anyhow::Error> {
+        let name = path.file_name().unwrap_or_default().to_string_lossy();
+        let staging = self.dir.join(format!(".{}.{}.tmp", name, Uuid::new_v4()));
+        let result = fs::OpenOptions::new()
+            .create_new(true)
+            .write(true)
+            .mode(0o600)
+            .open(&staging)
+            .and_then(|mut f| f.write_all(contents).and_then(|()| f.sync_all()))
+            .and_then(|()| fs::rename(&staging, path));
+        if result.is_err() {
+            let _ = fs::remove_file(&staging);
+        }
+        Ok(result?)
+    }
+}
+
+/// Splits a stored secret into the string or binary value it was put as.
+fn value(
+    secret_string: Option<String>,
+    secret_binary: Option<bytes::Bytes>,
+) -> Result<Vec<u8>, anyhow::Error> {
+    match (secret_string, secret_binary) {
+        (Some(s), None) => Ok(s.into_bytes()),
+        (None, Some(b)) => Ok(b.to_vec()),
+        _ => anyhow::bail!("Exactly one of a string or binary secret value is required"),
+    }
+}
+
+#[async_trait]
+impl SecretsBackend for LocalBackend {
+    async fn get_secret_value(
+        &self,
+        request: GetSecretValueRequest,
+    ) -> Result<GetSecretValueResponse, anyhow::Error> {
+        if request.version_id.is_some()
+            || request.version_stage.as_deref().unwrap_or("AWSCURRENT") != "AWSCURRENT"
+        {
+            anyhow::bail!("The local backend only keeps the current version of the secret");
+        }
+        let path = self.path(&request.secret_id);
+        log::debug!("Reading {}", path.display());
+        let contents =
+            match fs::read(&path) {
+                Ok(contents) => contents,
+                Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                    return Err(RusotoError::Service(GetSecretValueError::ResourceNotFound(
+                        format!("{} does not exist", path.display()),
+                    ))
+                    .into())
+                }
+                Err(e) => return Err(e.into()),
+            };
+        // Compressed secrets aren't valid utf-8, which tells them apart from plain JSON. JSON
+        // put as a binary value reads back as a string, which decodes the same.
+        let (secret_string, secret_binary) = match String::from_utf8(contents) {
+            Ok(s) => (Some(s), None),
+            Err(e) => (None, Some(e.into_bytes().into())),
+        };
+        Ok(GetSecretValueResponse {
+            name: Some(request.secret_id),
+            secret_binary,
+            secret_string,
+            ..Default::default()
+        })
+    }
+
+    async fn put_secret_value(
+        &self,
+        request: PutSecretValueRequest,
+    ) -> Result<PutSecretValueResponse, anyhow::Error> {
+        if !is_current_stage(request.version_stages.as_deref()) {
+            anyhow::bail!(
+                "The local backend only keeps the current version, so it can't stage one"
+            );
+        }
+        if !self.path(&request.secret_id).exists() {
+            return Err(
+                RusotoError::Service(PutSecretValueError::ResourceNotFound(format!(
+                    "{} does not exist",
+                    self.path(&request.secret_id).display()
+                )))
+                .into(),
+            );
+        }
+        log::debug!("Writing {}", self.path(&request.secret_id).display());
+        let contents = value(request.secret_string, request.secret_binary)?;
+        self.write(&request.secret_id, &contents)?;
+        Ok(PutSecretValueResponse {
+            name: Some(request.secret_id),
+            version_id: request.client_request_token,
+            ..Default::default()
+        })
+    }
+
+    async fn create_secret(
+        &self,
+        request: CreateSecretRequest,
+    ) -> Result<CreateSecretResponse, anyhow::Error> {
+        if self.path(&request.name).exists() {
+            return Err(
+                RusotoError::Service(CreateSecretError::ResourceExists(format!(
+                    "{} already exists",
+                    self.path(&request.name).display()
+                )))
+                .into(),
+            );
+        }
+        log::debug!("Creating {}", self.path(&request.name).display());
+        let contents = value(request.secret_string, request.secret_binary)?;
+        fs::create_dir_all(&self.dir)?;
+        self.write(&request.name, &contents)?;
+        Ok(CreateSecretResponse {
+            name: Some(request.name),
+            version_id: request.client_request_token,
+            ..Default::default()
+        })
+    }
+
+    async fn tag_resource(&self, request: TagResourceRequest) -> Result<(), anyhow::Error> {
+        if !self.path(&request.secret_id).exists() {
+            return Err(
+                RusotoError::Service(TagResourceError::ResourceNotFound(format!(
+                    "{} does not exist",
+                    self.path(&request.secret_id).display()
+                )))
+                .into(),
+            );
+        }
+        let path = self.tags_path(&request.secret_id);
+        log::debug!("Writing {}", path.display());
+        let mut tags = self.read_tags(&request.secret_id)?;
+        for tag in request.tags {
+            tags.insert(tag.key.unwrap_or_default(), tag.value.unwrap_or_default());
+        }
+        self.replace(&path, &serde_json::to_vec_pretty(&tags)?)
+    }
+
+    async fn describe_secret(
+        &self,
+        request: DescribeSecretRequest,
+    ) -> Result<DescribeSecretResponse, anyhow::Error> {
+        if !self.path(&request.secret_id).exists() {
+            return Err(
+                RusotoError::Service(DescribeSecretError::ResourceNotFound(format!(
+                    "{} does not exist",
+                    self.path(&request.secret_id).display()
+                )))
+                .into(),
+            );
+        }
+        let tags = self
+            .read_tags(&request.secret_id)?
+            .into_iter()
+            .map(|(key, value)| Tag {
+                key: Some(key),
+                value: Some(value),
+            })
+            .collect();
+        Ok(DescribeSecretResponse {
+            name: Some(request.secret_id),
+            tags: Some(tags),
+            ..Default::default()
+        })
+    }
+
+    async fn list_secrets(
+        &self,
+        request: ListSecretsRequest,
+    ) -> Result<ListSecretsResponse, anyhow::Error> {
+        // Like Secrets Manager, a name filter keeps the secrets whose names start with it.
+        let prefixes = request
+            .filters
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|filter| filter.key.as_deref() == Some("name"))
+            .flat_map(|filter| filter.values.unwrap_or_default())
+            .collect::<Vec<_>>();
+        let entries = match fs::read_dir(&self.dir) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Default::default()),
+            Err(e) => return Err(e).with_context(|| format!("{}", self.dir.display())),
+        };
+        let mut secrets = Vec::new();
+        for entry in entries {
+            let name = entry?.file_name().to_string_lossy().into_owned();
+            // Files being written are hidden, so they are never listed.
+            let secret_id = match name.strip_suffix(".json") {
+                Some(secret_id) if !secret_id.starts_with('.') => secret_id,
+                _ => continue,
+            };
+            if prefixes
+                .iter()
+                .all(|prefix| secret_id.starts_with(prefix.as_str()))
+            {
+                secrets.push(SecretListEntry {
+                    name: Some(secret_id.to_string()),
+                    ..Default::default()
+                });
+            }
+        }
+        secrets.sort_by(|a, b| a.name.cmp(&b.name));
+        Ok(ListSecretsResponse {
+            next_token: None,
+            secret_list: Some(secrets),
+        })
+    }
+}
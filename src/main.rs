@@ -1,4 +1,4 @@
-//! ssh-keys
+//! ssh-keys command line interface
 
 #![cfg(unix)]
 #![deny(clippy::all)]
@@ -9,200 +9,914 @@
 #![deny(unsafe_code)]
 #![deny(unused_imports)]
 
-use std::collections::HashMap;
-use std::fs;
-use std::io::{self, Write as _};
-use std::os::unix::fs::OpenOptionsExt as _;
+use std::env;
+use std::ffi::OsString;
+use std::io;
+use std::path::PathBuf;
 use std::process::exit;
+use std::time::Duration;
 
 use anyhow::Context as _;
+use glob::Pattern;
+use log::{Level, LevelFilter, Metadata, Record};
 use rusoto_core::Region;
-use rusoto_credential::ProfileProvider;
-use rusoto_secretsmanager::*;
-use uuid::Uuid;
+use rusoto_credential::ProvideAwsCredentials as _;
+use rusoto_secretsmanager::SecretsManagerClient;
+use rusoto_ssm::SsmClient;
+use serde_json::json;
+use ssh_keys::{
+    default_aws_config_path, default_aws_credentials_path, default_cache_dir, default_config_path,
+    profile_names, profile_region, BackendKind, Cache, ColorChoice, Config, Credentials,
+    CredentialsSource, Explain, Failure, GetOpt, KeyType, LocalBackend, OutputFormat, PutOpt,
+    Retry, ScanOpt, SecretsBackend, SsmBackend, Timeout, DEFAULT_PROFILE,
+};
 
-use std::path::PathBuf;
+use structopt::clap::Shell;
 use structopt::StructOpt;
 
-type Files = HashMap<String, String>;
-
 #[derive(Debug, StructOpt)]
 struct Opt {
-    /// Name of AWS profile (defined in ~/.aws/config) to use for credentials
-    #[structopt(long, default_value = "bcmyers")]
-    aws_profile: String,
+    /// Read defaults for these options from this TOML file instead of
+    /// ~/.config/ssh-keys/config.toml; options given on the command line take precedence
+    #[structopt(long)]
+    config: Option<PathBuf>,
+
+    /// How many times to retry an AWS request that was throttled or failed transiently
+    #[structopt(long, default_value = "3")]
+    max_retries: u32,
+
+    /// Where to store the secret: secrets-manager; ssm for a SecureString parameter in
+    /// Parameter Store named by --secret-id; or local for a JSON file in --path
+    #[structopt(long, default_value = "secrets-manager")]
+    backend: BackendKind,
+
+    /// Directory holding the secret files of the local backend, as <secret-id>.json
+    #[structopt(long, required_if("backend", "local"))]
+    path: Option<PathBuf>,
+
+    /// How to reach AWS, for the secrets-manager and ssm backends
+    #[structopt(flatten)]
+    aws: AwsOpt,
 
     /// ID of AWS secret where ssh keys are stored
-    #[structopt(long, default_value = "ssh-keys")]
+    #[structopt(long, env = "SSH_KEYS_SECRET_ID", default_value = "ssh-keys")]
     secret_id: String,
 
+    /// Give up on an AWS request that hasn't completed after this many seconds
+    #[structopt(long, default_value = "30")]
+    timeout: u64,
+
+    /// Reuse the current secret value fetched by an earlier command for this many seconds,
+    /// keeping it in ~/.cache/ssh-keys; commands that change the secret always discard it
+    #[structopt(long)]
+    cache_ttl: Option<u64>,
+
+    /// Fetch the secret even if a cached value is still fresh
+    #[structopt(long)]
+    no_cache: bool,
+
+    /// Print more detail, such as each AWS request made and each file written
+    #[structopt(short, long, global = true, conflicts_with = "quiet")]
+    verbose: bool,
+
+    /// Only print errors and the requested output, without summaries or warnings
+    #[structopt(short, long, global = true)]
+    quiet: bool,
+
+    /// How to print results: text, or json for scripts (human messages then go to stderr)
+    #[structopt(long, default_value = "text")]
+    output: OutputFormat,
+
+    /// When to color output: auto (only on a terminal and without NO_COLOR), always or never
+    #[structopt(long, global = true, default_value = "auto")]
+    color: ColorChoice,
+
+    /// Skip interactive confirmation prompts (required when stdin is not a terminal)
+    #[structopt(short, long, global = true)]
+    yes: bool,
+
+    /// Show the change that a command such as put, delete or rotate would make to the secret
+    /// without making it
+    #[structopt(long, global = true)]
+    dry_run: bool,
+
     /// Command
     #[structopt(subcommand)]
     command: Command,
 }
 
+/// Top-level options that default from an environment variable, by their long flag name.
+const ENV_SETTINGS: &[(&str, &str)] = &[
+    ("aws-profile", "AWS_PROFILE"),
+    ("secret-id", "SSH_KEYS_SECRET_ID"),
+];
+
+/// Parses the command line, taking the value of any top-level option it doesn't give from its
+/// environment variable, or else from the config file if that sets it.
+fn parse_args() -> Result<Opt, anyhow::Error> {
+    let args = env::args_os().collect::<Vec<_>>();
+    let matches = Opt::clap()
+        .get_matches_from_safe(&args)
+        .unwrap_or_else(|e| usage_error(e));
+    let opt = Opt::from_clap(&matches);
+    let mut config = match (&opt.config, default_config_path()) {
+        (Some(path), _) => Config::load(path, true)?,
+        (None, Some(path)) => Config::load(&path, false)?,
+        (None, None) => Config::default(),
+    };
+    // A value clap took from the environment doesn't count as an occurrence of the option, so
+    // the config file must be kept from overriding it here.
+    for (setting, var) in ENV_SETTINGS {
+        if env::var_os(var).is_some() {
+            config.remove(setting);
+        }
+    }
+    let defaults = config.args(&matches);
+    if defaults.is_empty() {
+        return Ok(opt);
+    }
+    // The settings go before the real arguments, where top-level options belong.
+    let args = args
+        .iter()
+        .take(1)
+        .cloned()
+        .chain(defaults.into_iter().map(OsString::from))
+        .chain(args.iter().skip(1).cloned());
+    Opt::from_iter_safe(args).map_err(|e| usage_error(e))
+}
+
+/// Exits after printing a command line parsing error, or the help or version asked for.
+fn usage_error(e: structopt::clap::Error) -> ! {
+    if !e.use_stderr() {
+        e.exit();
+    }
+    if requested_output() == OutputFormat::Json {
+        // The message starts with "error: " and goes on to the usage.
+        let message = e.message.lines().next().unwrap_or_default();
+        let message = message.trim_start_matches("error: ").to_string();
+        fail(&Failure::Usage.error(message), OutputFormat::Json);
+    }
+    eprintln!("{}", e.message);
+    exit(Failure::Usage.exit_code());
+}
+
+/// Options for connecting to AWS
+#[derive(Debug, StructOpt)]
+struct AwsOpt {
+    /// Name of AWS profile (defined in ~/.aws/config) to use for credentials [default: bcmyers]
+    #[structopt(long, env = "AWS_PROFILE")]
+    aws_profile: Option<String>,
+
+    /// Where to get AWS credentials from: chain, auto, env or profile; chain tries the
+    /// environment variables, then the profile, then container and instance metadata, as the
+    /// AWS SDKs do, while auto uses the environment variables only if AWS_ACCESS_KEY_ID is set
+    /// and --aws-profile is not given
+    #[structopt(long, default_value = "chain")]
+    credentials: CredentialsSource,
+
+    /// AWS region where the secret lives (e.g. us-west-2); defaults to AWS_REGION, then
+    /// AWS_DEFAULT_REGION, then the region of the profile in ~/.aws/config, then us-east-1
+    #[structopt(long)]
+    aws_region: Option<Region>,
+
+    /// ARN of an IAM role to assume (using the credentials above) before accessing the secret
+    #[structopt(long)]
+    assume_role_arn: Option<String>,
+
+    /// Session name to use when assuming a role
+    #[structopt(long, default_value = "ssh-keys")]
+    role_session_name: String,
+
+    /// External ID required by the trust policy of the role to assume
+    #[structopt(long)]
+    external_id: Option<String>,
+
+    /// Serial number or ARN of the MFA device to authenticate with when assuming a role that
+    /// requires MFA; the code is asked for unless --mfa-token is given
+    #[structopt(long, requires = "assume-role-arn")]
+    mfa_serial: Option<String>,
+
+    /// Code currently shown by the MFA device given with --mfa-serial
+    #[structopt(long, requires = "mfa-serial")]
+    mfa_token: Option<String>,
+
+    /// Send requests to this endpoint instead of the standard one for the region, e.g. to
+    /// use LocalStack at http://localhost:4566
+    #[structopt(long)]
+    endpoint_url: Option<String>,
+}
+
 /// Command
 #[derive(Debug, StructOpt)]
 enum Command {
-    Get {
-        /// An empty output directory
-        outdir: PathBuf,
+    /// Get ssh keys
+    Get(GetOpt),
+
+    /// Download the whole secret into a single backup file
+    Backup {
+        /// File to write the backup to; must not already exist
+        out: PathBuf,
+    },
+
+    /// Write the contents of a single stored key to stdout
+    Cat {
+        /// Name of the key to print
+        name: String,
+
+        /// Decrypt the keys encrypted by put --passphrase with this passphrase; asked for on
+        /// the terminal if given without a value
+        #[structopt(long, require_equals = true)]
+        passphrase: Option<Option<String>>,
+    },
+
+    /// Write a completion script for bash, zsh, fish, powershell or elvish to stdout
+    Completions {
+        /// Shell to write the script for
+        #[structopt(possible_values = &Shell::variants())]
+        shell: Shell,
     },
 
+    /// Copy a single stored key into another secret
+    Copy {
+        /// Name of the key to copy
+        name: String,
+
+        /// ID of the secret to copy the key into
+        dest_secret_id: String,
+    },
+
+    /// Delete a single ssh key from the stored secret
+    Delete {
+        /// Name of the key to delete
+        name: String,
+    },
+
+    /// Compare a local directory to the stored secret, exiting non-zero if they differ
+    Diff {
+        /// Directory containing ssh keys to compare
+        indir: PathBuf,
+
+        /// Which files in indir to compare
+        #[structopt(flatten)]
+        scan_opt: ScanOpt,
+
+        /// Decrypt the keys encrypted by put --passphrase with this passphrase; asked for on
+        /// the terminal if given without a value
+        #[structopt(long, require_equals = true)]
+        passphrase: Option<Option<String>>,
+    },
+
+    /// Check that the region, credentials and secret are usable, printing a line for each
+    /// check and exiting non-zero if any fail; nothing is changed
+    Doctor,
+
+    /// Edit the stored secret as JSON in $EDITOR and upload the result
+    Edit,
+
+    /// Print an `export SSH_KEY_<NAME>="<base64>"` line for each stored key, for use with
+    /// `eval "$(ssh-keys export-env)"`
+    ///
+    /// NAME is the key name uppercased, with every character other than an ASCII letter or
+    /// digit replaced by an underscore, so id_ed25519.pub becomes SSH_KEY_ID_ED25519_PUB.
+    /// Fails if two keys would get the same name.
+    ExportEnv {
+        /// Decrypt the keys encrypted by put --passphrase with this passphrase; asked for on
+        /// the terminal if given without a value
+        #[structopt(long, require_equals = true)]
+        passphrase: Option<Option<String>>,
+    },
+
+    /// Print the SHA256 fingerprints of stored public keys
+    Fingerprint {
+        /// Decrypt the keys encrypted by put --passphrase with this passphrase; asked for on
+        /// the terminal if given without a value
+        #[structopt(long, require_equals = true)]
+        passphrase: Option<Option<String>>,
+    },
+
+    /// Show which keys were added, removed or modified in each of the latest versions of the
+    /// secret
+    History {
+        /// Number of versions to show, newest first; each one shown (and the one before the
+        /// oldest) is downloaded
+        #[structopt(long, default_value = "10")]
+        depth: usize,
+    },
+
+    /// List the names of stored ssh keys without downloading them
+    List {
+        /// Also print the size in bytes of each key
+        #[structopt(long)]
+        long: bool,
+        /// Instead of the keys in --secret-id, list every secret whose name starts with this
+        /// prefix, with the number of keys stored in each
+        #[structopt(long, conflicts_with = "long")]
+        secret_id_prefix: Option<String>,
+    },
+
+    /// Add the stored private keys to the running ssh-agent (found through SSH_AUTH_SOCK)
+    /// without writing them to disk
+    ///
+    /// Encrypted keys are decrypted with a passphrase asked for on the terminal. Only keys in
+    /// OpenSSH format can be loaded.
+    Load {
+        /// Only load keys whose names match this glob, e.g. 'id_*' (may be repeated)
+        #[structopt(long, number_of_values = 1)]
+        only: Vec<Pattern>,
+
+        /// Decrypt the keys encrypted by put --passphrase with this passphrase; asked for on
+        /// the terminal if given without a value
+        #[structopt(long, require_equals = true)]
+        passphrase: Option<Option<String>>,
+    },
+
+    /// List the profiles defined in ~/.aws/config and ~/.aws/credentials, marking the one
+    /// used for credentials with *
+    ///
+    /// Only reads the files; no AWS APIs are called.
+    Profiles,
+
     /// Put ssh keys
-    Put {
-        /// Directory containing ssh keys to put
+    Put(PutOpt),
+
+    /// Move every stored key into another secret, creating it if needed
+    Migrate {
+        /// ID of the secret to move the keys into
+        dest_secret_id: String,
+
+        /// Delete all keys from the source secret once they have been copied
+        #[structopt(long)]
+        delete_source: bool,
+    },
+
+    /// Remove the staging labels from old versions of the secret so that they can be deleted
+    ///
+    /// Versions labelled AWSCURRENT or AWSPREVIOUS are always kept.
+    PurgeVersions {
+        /// Number of labelled versions to keep, newest first
+        #[structopt(long, default_value = "2")]
+        keep: usize,
+    },
+
+    /// Add the public keys loaded into the running ssh-agent (found through SSH_AUTH_SOCK) to
+    /// the stored secret, as agent-<type>-<hash>.pub
+    ///
+    /// An agent never gives out private keys, so only the public keys and their comments are
+    /// stored.
+    PutFromAgent,
+
+    /// Read a single ssh key from stdin and add it to the stored secret
+    ///
+    /// Since stdin holds the key, confirmation can't be asked for interactively; pass --yes.
+    PutOne {
+        /// Name to store the key under
+        name: String,
+    },
+
+    /// Change the name of a stored key without changing its contents
+    Rename {
+        /// Current name of the key
+        from: String,
+
+        /// New name for the key
+        to: String,
+
+        /// Overwrite an existing key with the new name
+        #[structopt(long)]
+        force: bool,
+    },
+
+    /// List the regions the secret is replicated to, with the status of each replica
+    Replicas,
+
+    /// Replace the stored secret with the contents of a backup file
+    Restore {
+        /// Backup file written by the backup command
+        file: PathBuf,
+    },
+
+    /// Generate a new key pair and store it as name and name.pub, keeping the previous pair
+    /// as name.old and name.old.pub
+    Rotate {
+        /// Name to store the private key under
+        name: String,
+
+        /// Type of key to generate: ed25519 or rsa
+        #[structopt(long, default_value = "ed25519")]
+        key_type: KeyType,
+    },
+
+    /// Summarize the stored keys: how many of each kind, the size of the secret, the largest
+    /// and smallest keys and the types of the public keys
+    Stats,
+
+    /// Make the stored secret match a directory, skipping the upload if nothing changed
+    Sync {
+        /// Directory containing ssh keys to sync
         indir: PathBuf,
+
+        /// Which files in indir to sync
+        #[structopt(flatten)]
+        scan_opt: ScanOpt,
+
+        /// Encrypt the keys with this passphrase before storing them, as put --passphrase
+        /// does, and decrypt those already stored with it; asked for on the terminal if given
+        /// without a value
+        #[structopt(long, require_equals = true)]
+        passphrase: Option<Option<String>>,
+    },
+
+    /// Check that a local directory still matches the stored secret, exiting non-zero if not
+    Verify {
+        /// Directory containing ssh keys to check
+        dir: PathBuf,
+
+        /// Which files in dir to check
+        #[structopt(flatten)]
+        scan_opt: ScanOpt,
+
+        /// Decrypt the keys encrypted by put --passphrase with this passphrase; asked for on
+        /// the terminal if given without a value
+        #[structopt(long, require_equals = true)]
+        passphrase: Option<Option<String>>,
     },
+
+    /// List the stored versions of the secret
+    Versions,
 }
 
 #[tokio::main]
-async fn main() -> Result<(), anyhow::Error> {
+async fn main() {
+    let opt = parse_args().unwrap_or_else(|e| fail(&e, requested_output()));
+    let output = opt.output;
+    if let Err(e) = run(opt).await {
+        fail(&e, output);
+    }
+}
+
+/// Exits after printing `e`, as JSON on stderr with `--output json`, with the exit code for
+/// the kind of failure it is.
+fn fail(e: &anyhow::Error, output: OutputFormat) -> ! {
+    match output {
+        OutputFormat::Json => eprintln!("{}", ssh_keys::error_json(e)),
+        OutputFormat::Text => eprintln!("Error: {:?}", e),
+    }
+    exit(ssh_keys::classify(e).map_or(1, Failure::exit_code));
+}
+
+/// Whether the command line asks for `--output json`, for reporting errors from before it
+/// could be parsed.
+fn requested_output() -> OutputFormat {
+    let args = env::args().collect::<Vec<_>>();
+    let json = args
+        .windows(2)
+        .any(|pair| pair[0] == "--output" && pair[1] == "json")
+        || args.iter().any(|arg| arg == "--output=json");
+    if json {
+        OutputFormat::Json
+    } else {
+        OutputFormat::Text
+    }
+}
+
+/// Runs the command given on the command line.
+async fn run(opt: Opt) -> Result<(), anyhow::Error> {
     let Opt {
-        aws_profile,
+        aws,
+        backend,
+        cache_ttl,
+        color,
         command,
+        config: _,
+        dry_run,
+        max_retries,
+        no_cache,
+        output,
+        path,
+        quiet,
         secret_id,
-    } = Opt::from_args();
+        timeout,
+        verbose,
+        yes,
+    } = opt;
 
-    let dispatcher = rusoto_core::request::HttpClient::new()?;
-    let provider = ProfileProvider::with_default_credentials(aws_profile)?;
-    let client = SecretsManagerClient::new_with(dispatcher, provider, Region::UsEast1);
+    // Generating completions only needs the command line definition, not a backend.
+    if let Command::Completions { shell } = command {
+        Opt::clap().gen_completions_to(env!("CARGO_PKG_NAME"), shell, &mut io::stdout());
+        return Ok(());
+    }
+    // Commands that can't preview their changes mustn't make them when asked not to.
+    let previewable = matches!(
+        command,
+        Command::Put(_)
+            | Command::PutFromAgent
+            | Command::PutOne { .. }
+            | Command::Copy { .. }
+            | Command::Delete { .. }
+            | Command::Edit
+            | Command::Rename { .. }
+            | Command::Restore { .. }
+            | Command::Rotate { .. }
+            | Command::PurgeVersions { .. }
+            | Command::Sync { .. }
+            | Command::Migrate { .. }
+    );
+    if dry_run && !previewable {
+        return Err(Failure::Usage.error(
+            "--dry-run is only supported by commands that change the secret, such as put, delete \
+             and rotate"
+                .to_string(),
+        ));
+    }
+
+    color.apply();
+    log::set_boxed_logger(Box::new(Logger {
+        info_to_stderr: output == OutputFormat::Json,
+    }))?;
+    log::set_max_level(if verbose {
+        LevelFilter::Debug
+    } else if quiet {
+        LevelFilter::Error
+    } else {
+        LevelFilter::Info
+    });
+
+    if let Command::Profiles = command {
+        return profiles(&aws, output);
+    }
+
+    if let Command::Doctor = command {
+        if !doctor(backend, path, aws, secret_id, timeout, max_retries).await? {
+            exit(1);
+        }
+        return Ok(());
+    }
+
+    let (service, scope): (Box<dyn SecretsBackend>, _) = match backend {
+        BackendKind::Local => {
+            let path = path.context("--backend local requires --path")?;
+            let scope = format!("local {}", path.display());
+            (Box::new(LocalBackend::new(path)), scope)
+        }
+        _ => {
+            let scope = format!("{:?} {}", backend, resolve_region(&aws)?.name());
+            (connect_aws(backend, aws).await?, scope)
+        }
+    };
+    // Each attempt gets the full timeout, and a timed out attempt isn't retried.
+    let client = Retry::new(
+        Timeout::new(service, Duration::from_secs(timeout)),
+        max_retries,
+    );
+    // The cache sits outside the retries, so that one cached read replaces all attempts.
+    let ttl = cache_ttl.filter(|_| !no_cache).map(Duration::from_secs);
+    let client: Box<dyn SecretsBackend> = match default_cache_dir() {
+        Some(dir) => Box::new(Cache::new(client, dir, scope, ttl)),
+        None if ttl.is_some() => {
+            anyhow::bail!("--cache-ttl needs a cache directory; set XDG_CACHE_HOME or HOME")
+        }
+        None => Box::new(client),
+    };
 
     match command {
-        Command::Get { outdir } => get(&client, outdir, secret_id).await?,
-        Command::Put { indir } => put(&client, indir, secret_id).await?,
+        Command::Get(opt) => ssh_keys::get(&client, secret_id, opt, output).await?,
+        Command::Backup { out } => ssh_keys::backup(&client, secret_id, &out).await?,
+        Command::Cat { name, passphrase } => {
+            ssh_keys::cat(&client, secret_id, name, passphrase).await?
+        }
+        Command::Completions { .. } => unreachable!(),
+        Command::Copy {
+            name,
+            dest_secret_id,
+        } => ssh_keys::copy(&client, secret_id, name, dest_secret_id, yes, dry_run).await?,
+        Command::Delete { name } => {
+            ssh_keys::delete(&client, secret_id, name, yes, dry_run).await?
+        }
+        Command::Diff {
+            indir,
+            scan_opt,
+            passphrase,
+        } => {
+            if ssh_keys::diff(&client, secret_id, &indir, &scan_opt, passphrase).await? {
+                exit(1);
+            }
+        }
+        Command::Doctor => unreachable!(),
+        Command::Edit => ssh_keys::edit(&client, secret_id, yes, dry_run).await?,
+        Command::ExportEnv { passphrase } => {
+            ssh_keys::export_env(&client, secret_id, passphrase).await?
+        }
+        Command::Fingerprint { passphrase } => {
+            ssh_keys::fingerprint(&client, secret_id, passphrase).await?
+        }
+        Command::History { depth } => ssh_keys::history(&client, secret_id, depth, output).await?,
+        Command::List {
+            secret_id_prefix: Some(prefix),
+            ..
+        } => ssh_keys::list_matching(&client, prefix, output).await?,
+        Command::List { long, .. } => ssh_keys::list(&client, secret_id, long, output).await?,
+        Command::Load { only, passphrase } => {
+            ssh_keys::load(&client, secret_id, &only, passphrase).await?
+        }
+        Command::Profiles => unreachable!(),
+        Command::Put(opt) => ssh_keys::put(&client, secret_id, yes, dry_run, opt, output).await?,
+        Command::Migrate {
+            dest_secret_id,
+            delete_source,
+        } => {
+            ssh_keys::migrate(
+                &client,
+                secret_id,
+                dest_secret_id,
+                delete_source,
+                yes,
+                dry_run,
+            )
+            .await?
+        }
+        Command::PurgeVersions { keep } => {
+            ssh_keys::purge_versions(&client, secret_id, keep, yes, dry_run).await?
+        }
+        Command::PutFromAgent => ssh_keys::put_from_agent(&client, secret_id, yes, dry_run).await?,
+        Command::PutOne { name } => {
+            ssh_keys::put_one(&client, secret_id, name, yes, dry_run).await?
+        }
+        Command::Rename { from, to, force } => {
+            ssh_keys::rename(&client, secret_id, from, to, force, yes, dry_run).await?
+        }
+        Command::Replicas => ssh_keys::replicas(&client, secret_id, output).await?,
+        Command::Restore { file } => {
+            ssh_keys::restore(&client, secret_id, &file, yes, dry_run).await?
+        }
+        Command::Rotate { name, key_type } => {
+            ssh_keys::rotate(&client, secret_id, name, key_type, yes, dry_run).await?
+        }
+        Command::Stats => ssh_keys::stats(&client, secret_id, output).await?,
+        Command::Sync {
+            indir,
+            scan_opt,
+            passphrase,
+        } => {
+            ssh_keys::sync(
+                &client, secret_id, &indir, &scan_opt, passphrase, yes, dry_run,
+            )
+            .await?
+        }
+        Command::Verify {
+            dir,
+            scan_opt,
+            passphrase,
+        } => {
+            if !ssh_keys::verify(&client, secret_id, &dir, &scan_opt, passphrase).await? {
+                exit(1);
+            }
+        }
+        Command::Versions => ssh_keys::versions(&client, secret_id).await?,
     }
 
     Ok(())
 }
 
-async fn get(
-    client: &SecretsManagerClient,
-    outdir: PathBuf,
-    secret_id: String,
-) -> Result<(), anyhow::Error> {
-    if outdir.exists() {
-        if !outdir
-            .metadata()
-            .with_context(|| format!("{}", outdir.display()))?
-            .is_dir()
-        {
-            anyhow::bail!(
-                "Provided outdir {} is not an empty directory",
-                outdir.display()
-            );
-        }
-        if fs::read_dir(&outdir)?.count() != 0 {
-            anyhow::bail!(
-                "Provided outdir {} is not an empty directory",
-                outdir.display()
-            );
-        }
-    } else {
-        fs::create_dir_all(&outdir).with_context(|| format!("{}", outdir.display()))?;
+/// Connects to the AWS service holding the secret, with errors explained in terms of the
+/// credentials and region used.
+async fn connect_aws(
+    backend: BackendKind,
+    aws: AwsOpt,
+) -> Result<Box<dyn SecretsBackend>, anyhow::Error> {
+    let region = aws_region(&aws)?;
+    log::debug!("Using region {}", region.name());
+    if let Region::Custom { endpoint, .. } = &region {
+        log::debug!("Using endpoint {}", endpoint);
     }
-    let request = {
-        let mut r = GetSecretValueRequest::default();
-        r.secret_id = secret_id;
-        r
+    let provider = credentials_provider(&aws, &region)?;
+    if aws.assume_role_arn.is_some() {
+        // Assume the role up front so that a denied request fails with a clear message
+        // rather than as an error from the first Secrets Manager call.
+        provider
+            .credentials()
+            .await
+            .with_context(|| format!("Failed to assume role with {}", provider))?;
+    }
+    connect_with(backend, provider, region)
+}
+
+/// Connects to the AWS service holding the secret in `region` with credentials from
+/// `provider`, which keeps any it has already fetched, such as those of an assumed role.
+fn connect_with(
+    backend: BackendKind,
+    provider: Credentials,
+    region: Region,
+) -> Result<Box<dyn SecretsBackend>, anyhow::Error> {
+    let dispatcher = rusoto_core::request::HttpClient::new()?;
+    // Info messages go to stdout, where they would end up mixed into the output of e.g. cat.
+    log::debug!("Using AWS credentials from {}", provider);
+    let description = provider.to_string();
+    let explain_region = region.clone();
+    let service: Box<dyn SecretsBackend> = match backend {
+        BackendKind::Ssm => Box::new(SsmBackend::new(SsmClient::new_with(
+            dispatcher, provider, region,
+        ))),
+        _ => Box::new(SecretsManagerClient::new_with(dispatcher, provider, region)),
     };
-    let response = client.get_secret_value(request).await?;
-    let s = response
-        .secret_string
-        .ok_or_else(|| anyhow::anyhow!("Expected secret_string in response but did not get one"))?;
-    let files = serde_json::from_str::<Files>(&s)?;
-    for (k, v) in files {
-        let path = outdir.join(&k);
-        let mode = if k.ends_with(".pub") || k.ends_with(".public") {
-            0o444
-        } else {
-            0o400
+    Ok(Box::new(Explain::new(
+        service,
+        &description,
+        &explain_region,
+    )))
+}
+
+/// The region requests are sent to, at the endpoint given with --endpoint-url if any.
+fn aws_region(aws: &AwsOpt) -> Result<Region, anyhow::Error> {
+    let region = resolve_region(aws)?;
+    Ok(match &aws.endpoint_url {
+        Some(endpoint) => Region::Custom {
+            name: region.name().to_string(),
+            endpoint: endpoint.clone(),
+        },
+        None => region,
+    })
+}
+
+/// Prints the profiles defined in the AWS shared config and credentials files, marking the
+/// one ssh-keys uses.
+fn profiles(aws: &AwsOpt, output: OutputFormat) -> Result<(), anyhow::Error> {
+    let current = aws.aws_profile.as_deref().unwrap_or(DEFAULT_PROFILE);
+    let profiles = profile_names(
+        default_aws_config_path().as_deref(),
+        default_aws_credentials_path().as_deref(),
+    )?;
+    if output == OutputFormat::Json {
+        let profiles = profiles
+            .iter()
+            .map(|profile| {
+                json!({
+                    "name": profile.name,
+                    "current": profile.name == current,
+                    "config": profile.in_config,
+                    "credentials": profile.in_credentials,
+                })
+            })
+            .collect::<Vec<_>>();
+        println!("{}", json!({ "current": current, "profiles": profiles }));
+        return Ok(());
+    }
+    if profiles.is_empty() {
+        log::info!("No profiles found.");
+        return Ok(());
+    }
+    let width = profiles
+        .iter()
+        .map(|profile| profile.name.len())
+        .max()
+        .unwrap_or(0);
+    for profile in &profiles {
+        let marker = if profile.name == current { '*' } else { ' ' };
+        let files = match (profile.in_config, profile.in_credentials) {
+            (true, true) => "config, credentials",
+            (true, false) => "config",
+            _ => "credentials",
         };
-        let f = fs::OpenOptions::new()
-            .create_new(true)
-            .write(true)
-            .mode(mode)
-            .open(&path)
-            .with_context(|| format!("{}", path.display()))?;
-        let mut writer = io::BufWriter::new(f);
-        writer.write_all(v.as_bytes())?;
+        println!(
+            "{} {:width$}  ({})",
+            marker,
+            profile.name,
+            files,
+            width = width
+        );
+    }
+    if !profiles.iter().any(|profile| profile.name == current) {
+        log::warn!(
+            "Warning: the profile in use, {}, isn't defined in either file",
+            current
+        );
     }
     Ok(())
 }
 
-async fn put(
-    client: &SecretsManagerClient,
-    indir: PathBuf,
+/// Builds the credentials provider chosen by the options, assuming the role given with
+/// --assume-role-arn through STS in `region` if any.
+fn credentials_provider(aws: &AwsOpt, region: &Region) -> Result<Credentials, anyhow::Error> {
+    let provider = Credentials::new(aws.credentials, aws.aws_profile.clone())?;
+    let role_arn = match &aws.assume_role_arn {
+        Some(role_arn) => role_arn,
+        None => return Ok(provider),
+    };
+    let mfa = match (&aws.mfa_serial, &aws.mfa_token) {
+        (Some(serial), Some(token)) => Some((serial.clone(), token.trim().to_string())),
+        (Some(serial), None) => {
+            let prompt = format!("MFA code for {}: ", serial);
+            let token = rpassword::prompt_password(prompt)
+                .context("Failed to read the MFA code; pass it with --mfa-token")?;
+            Some((serial.clone(), token.trim().to_string()))
+        }
+        (None, _) => None,
+    };
+    provider.assume_role(
+        region.clone(),
+        role_arn.clone(),
+        aws.role_session_name.clone(),
+        aws.external_id.clone(),
+        mfa,
+    )
+}
+
+/// Runs the checks of the doctor command, bypassing the cache, and returns whether they all
+/// passed. The checks of the secret are skipped if the region or credentials aren't usable.
+async fn doctor(
+    backend: BackendKind,
+    path: Option<PathBuf>,
+    aws: AwsOpt,
     secret_id: String,
-) -> Result<(), anyhow::Error> {
-    if !indir.metadata()?.is_dir() {
-        anyhow::bail!("Provided indir {} is not a directory", indir.display());
-    }
-    let mut map = HashMap::new();
-    for entry in fs::read_dir(&indir)? {
-        let entry = entry?;
-        if !entry
-            .metadata()
-            .with_context(|| format!("{}", entry.path().display()))?
-            .is_file()
-        {
-            continue;
-        }
-        let k = entry
-            .path()
-            .file_name()
-            .expect("Cannot fail")
-            .to_str()
-            .ok_or_else(|| {
-                anyhow::anyhow!(
-                    "File {} contains invalid utf-8 in it's filename",
-                    entry.path().display()
-                )
-            })?
-            .to_string();
-        let v = fs::read_to_string(entry.path())
-            .with_context(|| format!("{}", entry.path().display()))?;
-        map.insert(k, v);
-    }
-    let stdout = io::stdout();
-    let mut stdout = stdout.lock();
-    stdout
-        .write_all("Are you sure you want to override ssh-keys with the following:\n".as_bytes())?;
-    let mut keys = map.keys().collect::<Vec<_>>();
-    keys.sort();
-    for k in keys {
-        stdout.write_all(format!("  - {}\n", k).as_bytes())?;
-    }
-    stdout.write_all("This will delete the existing contents of ssh-keys\n".as_bytes())?;
-    drop(stdout);
-    let mut answer = String::new();
-    loop {
-        let stdout = io::stdout();
-        let mut stdout = stdout.lock();
-        stdout.write_all("yes/no: ".as_bytes())?;
-        stdout.flush()?;
-        drop(stdout);
-        io::stdin().read_line(&mut answer)?;
-        match answer.trim() {
-            "yes" | "y" | "Yes" | "YES" => break,
-            "no" | "n" | "No" | "NO" => {
-                println!("Cancelling and exiting.");
-                exit(0);
+    timeout: u64,
+    max_retries: u32,
+) -> Result<bool, anyhow::Error> {
+    let service: Box<dyn SecretsBackend> = match backend {
+        BackendKind::Local => {
+            let path = path.context("--backend local requires --path")?;
+            Box::new(LocalBackend::new(path))
+        }
+        _ => {
+            let region = match aws_region(&aws) {
+                Ok(region) => region,
+                Err(e) => return Ok(ssh_keys::report_check("Region", Err(e))),
+            };
+            ssh_keys::report_check("Region", Ok(region.name().to_string()));
+            // The provider is built once and reused for the secret's checks, so that an MFA
+            // code is only asked for, and used, once.
+            let provider = match credentials_provider(&aws, &region) {
+                Ok(provider) => provider,
+                Err(e) => return Ok(ssh_keys::report_check("Credentials", Err(e))),
+            };
+            let credentials = match provider.credentials().await {
+                Ok(_) => Ok(provider.to_string()),
+                Err(e) => Err(anyhow::Error::new(e)
+                    .context(format!("Failed to get credentials from {}", provider))),
+            };
+            if !ssh_keys::report_check("Credentials", credentials) {
+                return Ok(false);
             }
-            _ => answer.clear(),
+            connect_with(backend, provider, region)?
         }
-    }
-    let s = serde_json::to_string_pretty(&map)?;
-    let request = {
-        let mut r = PutSecretValueRequest::default();
-        r.client_request_token = Some(Uuid::new_v4().to_string());
-        r.secret_id = secret_id;
-        r.secret_string = Some(s);
-        r
     };
-    let response = client.put_secret_value(request).await?;
-    if let Some(version) = response.version_id {
-        println!("Secret version: {}", version);
+    let client = Retry::new(
+        Timeout::new(service, Duration::from_secs(timeout)),
+        max_retries,
+    );
+    Ok(ssh_keys::doctor(&client, secret_id).await)
+}
+
+/// Prints log messages from ssh-keys without decoration: summaries (info) to stdout alongside
+/// the output they summarize, and everything else to stderr.
+#[derive(Debug)]
+struct Logger {
+    /// Send summaries to stderr too, because stdout holds JSON
+    info_to_stderr: bool,
+}
+
+impl log::Log for Logger {
+    fn enabled(&self, metadata: &Metadata<'_>) -> bool {
+        // Only show messages from ssh-keys itself, not the debug output of rusoto and hyper.
+        metadata.level() <= log::max_level() && metadata.target().starts_with("ssh_keys")
     }
-    Ok(())
+
+    fn log(&self, record: &Record<'_>) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+        match record.level() {
+            Level::Info if !self.info_to_stderr => println!("{}", record.args()),
+            Level::Debug | Level::Trace => eprintln!("debug: {}", record.args()),
+            _ => eprintln!("{}", record.args()),
+        }
+    }
+
+    fn flush(&self) {}
+}
+
+/// Picks the region from the command line, falling back to the standard AWS environment
+/// variables, then to the region set for the profile in ~/.aws/config, and finally to
+/// us-east-1.
+fn resolve_region(aws: &AwsOpt) -> Result<Region, anyhow::Error> {
+    if let Some(region) = &aws.aws_region {
+        return Ok(region.clone());
+    }
+    for var in &["AWS_REGION", "AWS_DEFAULT_REGION"] {
+        if let Ok(value) = env::var(var) {
+            return value
+                .parse::<Region>()
+                .with_context(|| format!("Invalid region in environment variable {}", var));
+        }
+    }
+    let profile = aws.aws_profile.as_deref().unwrap_or(DEFAULT_PROFILE);
+    if let Some(path) = default_aws_config_path() {
+        if let Some(region) = profile_region(&path, profile)? {
+            log::debug!(
+                "Using the region of profile {} in {}",
+                profile,
+                path.display()
+            );
+            return Ok(region);
+        }
+    }
+    Ok(Region::UsEast1)
 }
@@ -0,0 +1,82 @@
+//! Formats for the results of commands.
+
+use std::io::{self, Write};
+use std::str::FromStr;
+
+use owo_colors::Stream;
+
+/// How commands print their results on stdout.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum OutputFormat {
+    /// Human-readable text
+    Text,
+    /// Structured JSON, with human-readable messages moved to stderr
+    Json,
+}
+
+impl OutputFormat {
+    /// Where human-readable messages such as confirmation prompts go: stdout next to text
+    /// results, but stderr when stdout is reserved for JSON.
+    pub(crate) fn messages(self) -> Box<dyn Write> {
+        match self {
+            OutputFormat::Text => Box::new(io::stdout()),
+            OutputFormat::Json => Box::new(io::stderr()),
+        }
+    }
+
+    /// The stream [`messages`](OutputFormat::messages) writes to, for deciding whether to
+    /// color them.
+    pub(crate) fn messages_stream(self) -> Stream {
+        match self {
+            OutputFormat::Text => Stream::Stdout,
+            OutputFormat::Json => Stream::Stderr,
+        }
+    }
+}
+
+impl FromStr for OutputFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "text" => Ok(OutputFormat::Text),
+            "json" => Ok(OutputFormat::Json),
+            _ => anyhow::bail!("Expected one of text or json but got {:?}", s),
+        }
+    }
+}
+
+/// Whether to color human-readable output.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ColorChoice {
+    /// Color output going to a terminal, unless `NO_COLOR` is set
+    Auto,
+    /// Always color output
+    Always,
+    /// Never color output
+    Never,
+}
+
+impl ColorChoice {
+    /// Makes this choice for all output printed from now on.
+    pub fn apply(self) {
+        match self {
+            ColorChoice::Auto => owo_colors::unset_override(),
+            ColorChoice::Always => owo_colors::set_override(true),
+            ColorChoice::Never => owo_colors::set_override(false),
+        }
+    }
+}
+
+impl FromStr for ColorChoice {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "auto" => Ok(ColorChoice::Auto),
+            "always" => Ok(ColorChoice::Always),
+            "never" => Ok(ColorChoice::Never),
+            _ => anyhow::bail!("Expected one of auto, always or never but got {:?}", s),
+        }
+    }
+}
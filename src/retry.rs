@@ -0,0 +1,231 @@
+//! Retrying of throttled and transient failures.
+
+use std::future::Future;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use rand::Rng as _;
+use rusoto_core::RusotoError;
+use rusoto_secretsmanager::{
+    CreateSecretRequest, CreateSecretResponse, DescribeSecretError, DescribeSecretRequest,
+    DescribeSecretResponse, GetSecretValueError, GetSecretValueRequest, GetSecretValueResponse,
+    ListSecretVersionIdsError, ListSecretVersionIdsRequest, ListSecretVersionIdsResponse,
+    ListSecretsError, ListSecretsRequest, ListSecretsResponse, PutSecretValueError,
+    PutSecretValueRequest, PutSecretValueResponse, ReplicateSecretToRegionsRequest,
+    ReplicateSecretToRegionsResponse, TagResourceRequest, UpdateSecretRequest,
+    UpdateSecretResponse, UpdateSecretVersionStageRequest, UpdateSecretVersionStageResponse,
+};
+
+use crate::backend::SecretsBackend;
+
+/// Delay before the first retry; each further retry waits twice as long.
+const BASE_DELAY: Duration = Duration::from_millis(200);
+
+/// A backend that retries reads, writes, descriptions and listings of the secret when they
+/// fail because of throttling or a transient server or network error.
+///
+/// Other errors, such as access being denied or the secret not existing, are returned
+/// straight away.
+#[derive(Debug)]
+pub struct Retry<B> {
+    inner: B,
+    max_retries: u32,
+}
+
+impl<B: SecretsBackend> Retry<B> {
+    /// Wraps `inner`, retrying each failed call up to `max_retries` times.
+    pub fn new(inner: B, max_retries: u32) -> Self {
+        Retry { inner, max_retries }
+    }
+
+    /// Calls `call` until it succeeds, fails with an error `is_retryable` rejects, or has
+    /// been retried `max_retries` times.
+    async fn retry<T, F, Fut>(
+        &self,
+        operation: &str,
+        is_retryable: fn(&anyhow::Error) -> bool,
+        mut call: F,
+    ) -> Result<T, anyhow::Error>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<T, anyhow::Error>>,
+    {
+        let mut attempt = 0;
+        loop {
+            match call().await {
+                Err(e) if attempt < self.max_retries && is_retryable(&e) => {
+                    attempt += 1;
+                    // Full jitter, so that clients throttled at the same time don't all retry
+                    // at the same time too.
+                    let max_delay = BASE_DELAY * 2u32.pow(attempt - 1);
+                    let delay = rand::thread_rng().gen_range(Duration::ZERO..=max_delay);
+                    log::warn!(
+                        "{} failed ({}); retrying in {}ms (retry {} of {})",
+                        operation,
+                        e,
+                        delay.as_millis(),
+                        attempt,
+                        self.max_retries
+                    );
+                    tokio::time::sleep(delay).await;
+                }
+                result => return result,
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl<B: SecretsBackend> SecretsBackend for Retry<B> {
+    async fn get_secret_value(
+        &self,
+        request: GetSecretValueRequest,
+    ) -> Result<GetSecretValueResponse, anyhow::Error> {
+        self.retry(
+            "GetSecretValue",
+            is_retryable::<GetSecretValueError>,
+            || self.inner.get_secret_value(request.clone()),
+        )
+        .await
+    }
+
+    async fn put_secret_value(
+        &self,
+        request: PutSecretValueRequest,
+    ) -> Result<PutSecretValueResponse, anyhow::Error> {
+        // The request carries a client request token, so a retry of a write that actually
+        // succeeded doesn't create a second version.
+        self.retry(
+            "PutSecretValue",
+            is_retryable::<PutSecretValueError>,
+            || self.inner.put_secret_value(request.clone()),
+        )
+        .await
+    }
+
+    async fn list_secret_version_ids(
+        &self,
+        request: ListSecretVersionIdsRequest,
+    ) -> Result<ListSecretVersionIdsResponse, anyhow::Error> {
+        self.retry(
+            "ListSecretVersionIds",
+            is_retryable::<ListSecretVersionIdsError>,
+            || self.inner.list_secret_version_ids(request.clone()),
+        )
+        .await
+    }
+
+    async fn create_secret(
+        &self,
+        request: CreateSecretRequest,
+    ) -> Result<CreateSecretResponse, anyhow::Error> {
+        self.inner.create_secret(request).await
+    }
+
+    async fn update_secret(
+        &self,
+        request: UpdateSecretRequest,
+    ) -> Result<UpdateSecretResponse, anyhow::Error> {
+        self.inner.update_secret(request).await
+    }
+
+    async fn tag_resource(&self, request: TagResourceRequest) -> Result<(), anyhow::Error> {
+        self.inner.tag_resource(request).await
+    }
+
+    async fn update_secret_version_stage(
+        &self,
+        request: UpdateSecretVersionStageRequest,
+    ) -> Result<UpdateSecretVersionStageResponse, anyhow::Error> {
+        self.inner.update_secret_version_stage(request).await
+    }
+
+    async fn describe_secret(
+        &self,
+        request: DescribeSecretRequest,
+    ) -> Result<DescribeSecretResponse, anyhow::Error> {
+        self.retry(
+            "DescribeSecret",
+            is_retryable::<DescribeSecretError>,
+            || self.inner.describe_secret(request.clone()),
+        )
+        .await
+    }
+
+    async fn replicate_secret_to_regions(
+        &self,
+        request: ReplicateSecretToRegionsRequest,
+    ) -> Result<ReplicateSecretToRegionsResponse, anyhow::Error> {
+        self.inner.replicate_secret_to_regions(request).await
+    }
+
+    async fn list_secrets(
+        &self,
+        request: ListSecretsRequest,
+    ) -> Result<ListSecretsResponse, anyhow::Error> {
+        self.retry("ListSecrets", is_retryable::<ListSecretsError>, || {
+            self.inner.list_secrets(request.clone())
+        })
+        .await
+    }
+
+    fn size_limit(&self) -> usize {
+        self.inner.size_limit()
+    }
+}
+
+/// Service errors that indicate a problem on the server's side.
+trait InternalError {
+    fn is_internal(&self) -> bool;
+}
+
+impl InternalError for GetSecretValueError {
+    fn is_internal(&self) -> bool {
+        matches!(self, GetSecretValueError::InternalServiceError(_))
+    }
+}
+
+impl InternalError for PutSecretValueError {
+    fn is_internal(&self) -> bool {
+        matches!(self, PutSecretValueError::InternalServiceError(_))
+    }
+}
+
+impl InternalError for DescribeSecretError {
+    fn is_internal(&self) -> bool {
+        matches!(self, DescribeSecretError::InternalServiceError(_))
+    }
+}
+
+impl InternalError for ListSecretsError {
+    fn is_internal(&self) -> bool {
+        matches!(self, ListSecretsError::InternalServiceError(_))
+    }
+}
+
+impl InternalError for ListSecretVersionIdsError {
+    fn is_internal(&self) -> bool {
+        matches!(self, ListSecretVersionIdsError::InternalServiceError(_))
+    }
+}
+
+/// Whether `e` is a failure of an operation with error type `E` that may succeed if the
+/// request is simply made again.
+fn is_retryable<E>(e: &anyhow::Error) -> bool
+where
+    E: InternalError + std::error::Error + Send + Sync + 'static,
+{
+    match e.downcast_ref::<RusotoError<E>>() {
+        Some(RusotoError::Service(e)) => e.is_internal(),
+        Some(RusotoError::HttpDispatch(_)) => true,
+        // Throttling isn't one of the modelled errors, so it shows up as an unknown
+        // response.
+        Some(RusotoError::Unknown(response)) => {
+            let status = response.status.as_u16();
+            status == 429
+                || status >= 500
+                || String::from_utf8_lossy(&response.body).contains("ThrottlingException")
+        }
+        _ => false,
+    }
+}
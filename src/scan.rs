@@ -0,0 +1,236 @@
+//! Reading a local directory or tar archive of keys into a set of files.
+
+use std::collections::HashMap;
+use std::convert::TryFrom as _;
+use std::fs;
+use std::io::{self, Read};
+use std::os::unix::fs::{MetadataExt as _, PermissionsExt as _};
+use std::path::{Component, Path};
+
+use anyhow::Context as _;
+use glob::Pattern;
+use structopt::StructOpt;
+
+use crate::files::{Entry, Files};
+
+/// Options controlling which files in a local directory are read
+#[derive(Debug, Default, StructOpt)]
+pub struct ScanOpt {
+    /// Only read files whose names match this glob (may be repeated)
+    #[structopt(long, number_of_values = 1)]
+    pub only: Vec<Pattern>,
+
+    /// Skip files whose names match this glob, e.g. 'known_hosts' or '*~' (may be repeated)
+    #[structopt(long, number_of_values = 1)]
+    pub exclude: Vec<Pattern>,
+
+    /// Also read files in subdirectories, keyed by their path relative to the directory with
+    /// / separators, e.g. config.d/host1
+    #[structopt(long)]
+    pub recursive: bool,
+
+    /// Skip symlinks instead of reading the files they point to
+    #[structopt(long)]
+    pub no_follow_symlinks: bool,
+
+    /// Also read files and directories whose names begin with a dot, such as .DS_Store
+    #[structopt(long)]
+    pub include_hidden: bool,
+
+    /// Fail if any file read is larger than this many bytes, which no SSH key should be; 0
+    /// disables the check
+    #[structopt(long, default_value = "1048576")]
+    pub max_file_size: u64,
+}
+
+impl ScanOpt {
+    /// Whether a file with this name should be read.
+    fn includes(&self, name: &str) -> bool {
+        (self.only.is_empty() || self.only.iter().any(|p| p.matches(name)))
+            && !self.exclude.iter().any(|p| p.matches(name))
+    }
+
+    /// Fails if the file at `path`, of `size` bytes, is larger than the maximum file size.
+    fn check_size(&self, path: &Path, size: u64) -> Result<(), anyhow::Error> {
+        if self.max_file_size != 0 && size > self.max_file_size {
+            anyhow::bail!(
+                "File {} is {} bytes, which is larger than --max-file-size of {} bytes; is this \
+                 the right directory?",
+                path.display(),
+                size,
+                self.max_file_size
+            );
+        }
+        Ok(())
+    }
+}
+
+/// Reads the regular files directly inside `dir` (or anywhere below it, with
+/// [`recursive`](ScanOpt::recursive)), keyed by relative path and recording their permissions
+/// and modification times.
+///
+/// Hidden files and directories are skipped unless
+/// [`include_hidden`](ScanOpt::include_hidden) is set. Symlinks to files are read as the file
+/// they point to, unless [`no_follow_symlinks`](ScanOpt::no_follow_symlinks) is set. Dangling
+/// symlinks and symlinks to directories are skipped with a warning.
+pub fn scan(dir: &Path, opt: &ScanOpt) -> Result<Files, anyhow::Error> {
+    if !dir
+        .metadata()
+        .with_context(|| format!("{}", dir.display()))?
+        .is_dir()
+    {
+        anyhow::bail!("Provided indir {} is not a directory", dir.display());
+    }
+    let mut map = HashMap::new();
+    // Directories still to read, with the key prefix of the files in them.
+    let mut pending = vec![(dir.to_path_buf(), String::new())];
+    while let Some((dir, prefix)) = pending.pop() {
+        for entry in fs::read_dir(&dir).with_context(|| format!("{}", dir.display()))? {
+            let entry = entry?;
+            let mut metadata = entry
+                .metadata()
+                .with_context(|| format!("{}", entry.path().display()))?;
+            let symlink = metadata.file_type().is_symlink();
+            if symlink && opt.no_follow_symlinks {
+                log::debug!("Skipping symlink {}", entry.path().display());
+                continue;
+            }
+            if symlink {
+                metadata = match fs::metadata(entry.path()) {
+                    Ok(metadata) => metadata,
+                    Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                        log::warn!("Skipping {}: dangling symlink", entry.path().display());
+                        continue;
+                    }
+                    Err(e) => return Err(e).with_context(|| format!("{}", entry.path().display())),
+                };
+            }
+            let name = entry.file_name();
+            let name = name.to_str().ok_or_else(|| {
+                anyhow::anyhow!(
+                    "File {} contains invalid utf-8 in it's filename",
+                    entry.path().display()
+                )
+            })?;
+            let k = format!("{}{}", prefix, name);
+            if name.starts_with('.') && !opt.include_hidden {
+                log::debug!("Skipping hidden {}", entry.path().display());
+                continue;
+            }
+            if symlink && metadata.is_dir() {
+                // Following these could read the same files twice, or loop forever.
+                log::warn!(
+                    "Skipping {}: symlink to a directory",
+                    entry.path().display()
+                );
+                continue;
+            }
+            if metadata.is_dir() && opt.recursive {
+                pending.push((entry.path(), format!("{}/", k)));
+                continue;
+            }
+            if !metadata.is_file() || !opt.includes(&k) {
+                continue;
+            }
+            opt.check_size(&entry.path(), metadata.len())?;
+            let v =
+                fs::read(entry.path()).with_context(|| format!("{}", entry.path().display()))?;
+            map.insert(
+                k,
+                Entry {
+                    contents: v,
+                    mode: Some(metadata.permissions().mode() & 0o7777),
+                    mtime: Some(metadata.mtime()),
+                    comments: None,
+                    encrypted: false,
+                },
+            );
+        }
+    }
+    Ok(map)
+}
+
+/// Reads the regular files at the top level of a tar archive (or anywhere in it, with
+/// [`recursive`](ScanOpt::recursive)) from a file or stdin, for `-`, keyed by entry name and
+/// recording their permissions and modification times. Hidden files, and files in hidden
+/// directories, are skipped unless [`include_hidden`](ScanOpt::include_hidden) is set. Other
+/// entries are skipped with a warning.
+pub fn scan_tar(archive: &Path, opt: &ScanOpt) -> Result<Files, anyhow::Error> {
+    let reader: Box<dyn Read> = if archive == Path::new("-") {
+        Box::new(io::stdin())
+    } else {
+        Box::new(fs::File::open(archive).with_context(|| format!("{}", archive.display()))?)
+    };
+    let mut archive = tar::Archive::new(reader);
+    let mut map = HashMap::new();
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        let path = entry.path()?.into_owned();
+        // Archives made with e.g. `tar -C dir -cf keys.tar .` name their entries ./name.
+        let components = path
+            .components()
+            .filter(|c| *c != Component::CurDir)
+            .collect::<Vec<_>>();
+        let entry_type = entry.header().entry_type();
+        if !entry_type.is_file() {
+            // Directories only give structure to the files in them when reading recursively.
+            let structural = components.is_empty() || (opt.recursive && entry_type.is_dir());
+            if !structural {
+                log::warn!("Skipping {}: not a regular file", path.display());
+            }
+            continue;
+        }
+        if components.len() != 1 && !opt.recursive {
+            log::warn!(
+                "Skipping {}: not at the top level of the archive",
+                path.display()
+            );
+            continue;
+        }
+        let mut names = Vec::with_capacity(components.len());
+        for component in &components {
+            match component {
+                Component::Normal(name) => names.push(name.to_str().ok_or_else(|| {
+                    anyhow::anyhow!(
+                        "Entry {} contains invalid utf-8 in it's name",
+                        path.display()
+                    )
+                })?),
+                _ => {
+                    log::warn!("Skipping {}: not a relative path", path.display());
+                    names.clear();
+                    break;
+                }
+            }
+        }
+        if names.is_empty() {
+            continue;
+        }
+        let k = names.join("/");
+        if names.iter().any(|name| name.starts_with('.')) && !opt.include_hidden {
+            log::debug!("Skipping hidden {}", path.display());
+            continue;
+        }
+        if !opt.includes(&k) {
+            continue;
+        }
+        opt.check_size(&path, entry.header().size()?)?;
+        let mode = entry.header().mode()? & 0o7777;
+        let mtime = entry.header().mtime()?;
+        let mut v = Vec::new();
+        entry
+            .read_to_end(&mut v)
+            .with_context(|| format!("{}", path.display()))?;
+        map.insert(
+            k,
+            Entry {
+                contents: v,
+                mode: Some(mode),
+                mtime: i64::try_from(mtime).ok(),
+                comments: None,
+                encrypted: false,
+            },
+        );
+    }
+    Ok(map)
+}
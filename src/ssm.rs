@@ -0,0 +1,344 @@
+//! SSM Parameter Store as a place to keep the secret.
+
+use std::fmt;
+
+use async_trait::async_trait;
+use rusoto_core::RusotoError;
+use rusoto_secretsmanager::{
+    CreateSecretError, CreateSecretRequest, CreateSecretResponse, DescribeSecretError,
+    DescribeSecretRequest, DescribeSecretResponse, GetSecretValueError, GetSecretValueRequest,
+    GetSecretValueResponse, ListSecretVersionIdsError, ListSecretVersionIdsRequest,
+    ListSecretVersionIdsResponse, PutSecretValueError, PutSecretValueRequest,
+    PutSecretValueResponse, SecretVersionsListEntry, Tag, TagResourceError, TagResourceRequest,
+};
+use rusoto_ssm::{
+    AddTagsToResourceError, AddTagsToResourceRequest, GetParameterError, GetParameterHistoryError,
+    GetParameterHistoryRequest, GetParameterRequest, ListTagsForResourceError,
+    ListTagsForResourceRequest, PutParameterError, PutParameterRequest, Ssm, SsmClient,
+};
+
+use crate::backend::{is_current_stage, SecretsBackend};
+
+/// Maximum size in bytes of a standard-tier parameter value, the tier parameters are created
+/// in unless the account defaults to another.
+const PARAMETER_SIZE_LIMIT: usize = 4096;
+
+/// Stores the secret as a `SecureString` parameter named by the secret id.
+///
+/// Parameter versions stand in for secret versions and parameter labels for staging labels.
+/// Errors with a Secrets Manager equivalent, such as the parameter not existing, are reported
+/// as that equivalent so that they are handled the same way for both backends.
+pub struct SsmBackend {
+    client: SsmClient,
+}
+
+impl SsmBackend {
+    /// Uses `client` to reach Parameter Store.
+    pub fn new(client: SsmClient) -> Self {
+        SsmBackend { client }
+    }
+}
+
+impl fmt::Debug for SsmBackend {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SsmBackend").finish()
+    }
+}
+
+#[async_trait]
+impl SecretsBackend for SsmBackend {
+    async fn get_secret_value(
+        &self,
+        request: GetSecretValueRequest,
+    ) -> Result<GetSecretValueResponse, anyhow::Error> {
+        log::debug!("GetParameter {}", request.secret_id);
+        // Parameter Store selects versions and labels with a suffix on the name. Every
+        // parameter's latest version is current, so that label needs no selector.
+        let name = match (request.version_id, request.version_stage.as_deref()) {
+            (Some(version), _) => format!("{}:{}", request.secret_id, version),
+            (None, Some(stage)) if stage != "AWSCURRENT" => {
+                format!("{}:{}", request.secret_id, stage)
+            }
+            _ => request.secret_id.clone(),
+        };
+        let response = self
+            .client
+            .get_parameter(GetParameterRequest {
+                name,
+                with_decryption: Some(true),
+            })
+            .await
+            .map_err(|e| {
+                convert(e, |e| match e {
+                    GetParameterError::ParameterNotFound(m)
+                    | GetParameterError::ParameterVersionNotFound(m) => {
+                        Ok(GetSecretValueError::ResourceNotFound(m))
+                    }
+                    GetParameterError::InternalServerError(m) => {
+                        Ok(GetSecretValueError::InternalServiceError(m))
+                    }
+                    e => Err(e),
+                })
+            })?;
+        let parameter = response.parameter.unwrap_or_default();
+        Ok(GetSecretValueResponse {
+            arn: parameter.arn,
+            created_date: parameter.last_modified_date,
+            name: parameter.name,
+            secret_string: parameter.value,
+            version_id: parameter.version.map(|v| v.to_string()),
+            ..Default::default()
+        })
+    }
+
+    async fn put_secret_value(
+        &self,
+        request: PutSecretValueRequest,
+    ) -> Result<PutSecretValueResponse, anyhow::Error> {
+        log::debug!("PutParameter {}", request.secret_id);
+        if !is_current_stage(request.version_stages.as_deref()) {
+            anyhow::bail!("Parameter Store makes every new version current, so it can't stage one");
+        }
+        let value = plain_value(request.secret_string, request.secret_binary.is_some())?;
+        // Overwriting creates a missing parameter, yet only creating the secret may do that.
+        self.client
+            .get_parameter(GetParameterRequest {
+                name: request.secret_id.clone(),
+                with_decryption: Some(false),
+            })
+            .await
+            .map_err(|e| {
+                convert(e, |e| match e {
+                    GetParameterError::ParameterNotFound(m) => {
+                        Ok(PutSecretValueError::ResourceNotFound(m))
+                    }
+                    GetParameterError::InternalServerError(m) => {
+                        Ok(PutSecretValueError::InternalServiceError(m))
+                    }
+                    e => Err(e),
+                })
+            })?;
+        let response = self
+            .client
+            .put_parameter(PutParameterRequest {
+                name: request.secret_id.clone(),
+                overwrite: Some(true),
+                type_: Some("SecureString".to_string()),
+                value,
+                ..Default::default()
+            })
+            .await
+            .map_err(|e| {
+                convert(e, |e| match e {
+                    PutParameterError::InternalServerError(m) => {
+                        Ok(PutSecretValueError::InternalServiceError(m))
+                    }
+                    e => Err(e),
+                })
+            })?;
+        Ok(PutSecretValueResponse {
+            name: Some(request.secret_id),
+            version_id: response.version.map(|v| v.to_string()),
+            ..Default::default()
+        })
+    }
+
+    async fn list_secret_version_ids(
+        &self,
+        request: ListSecretVersionIdsRequest,
+    ) -> Result<ListSecretVersionIdsResponse, anyhow::Error> {
+        log::debug!("GetParameterHistory {}", request.secret_id);
+        let response = self
+            .client
+            .get_parameter_history(GetParameterHistoryRequest {
+                max_results: request.max_results,
+                name: request.secret_id.clone(),
+                next_token: request.next_token,
+                with_decryption: Some(false),
+            })
+            .await
+            .map_err(|e| {
+                convert(e, |e| match e {
+                    GetParameterHistoryError::ParameterNotFound(m) => {
+                        Ok(ListSecretVersionIdsError::ResourceNotFound(m))
+                    }
+                    GetParameterHistoryError::InternalServerError(m) => {
+                        Ok(ListSecretVersionIdsError::InternalServiceError(m))
+                    }
+                    GetParameterHistoryError::InvalidNextToken(m) => {
+                        Ok(ListSecretVersionIdsError::InvalidNextToken(m))
+                    }
+                    e => Err(e),
+                })
+            })?;
+        let versions = response
+            .parameters
+            .unwrap_or_default()
+            .into_iter()
+            .map(|p| SecretVersionsListEntry {
+                created_date: p.last_modified_date,
+                version_id: p.version.map(|v| v.to_string()),
+                version_stages: p.labels.filter(|labels| !labels.is_empty()),
+                ..Default::default()
+            })
+            .collect();
+        Ok(ListSecretVersionIdsResponse {
+            name: Some(request.secret_id),
+            next_token: response.next_token,
+            versions: Some(versions),
+            ..Default::default()
+        })
+    }
+
+    async fn create_secret(
+        &self,
+        request: CreateSecretRequest,
+    ) -> Result<CreateSecretResponse, anyhow::Error> {
+        log::debug!("PutParameter {} (new)", request.name);
+        let value = plain_value(request.secret_string, request.secret_binary.is_some())?;
+        let tags = request.tags.map(|tags| {
+            tags.into_iter()
+                .map(|t| rusoto_ssm::Tag {
+                    key: t.key.unwrap_or_default(),
+                    value: t.value.unwrap_or_default(),
+                })
+                .collect()
+        });
+        let response = self
+            .client
+            .put_parameter(PutParameterRequest {
+                description: request.description,
+                key_id: request.kms_key_id,
+                name: request.name.clone(),
+                overwrite: Some(false),
+                tags,
+                type_: Some("SecureString".to_string()),
+                value,
+                ..Default::default()
+            })
+            .await
+            .map_err(|e| {
+                convert(e, |e| match e {
+                    PutParameterError::ParameterAlreadyExists(m) => {
+                        Ok(CreateSecretError::ResourceExists(m))
+                    }
+                    PutParameterError::InternalServerError(m) => {
+                        Ok(CreateSecretError::InternalServiceError(m))
+                    }
+                    e => Err(e),
+                })
+            })?;
+        Ok(CreateSecretResponse {
+            name: Some(request.name),
+            version_id: response.version.map(|v| v.to_string()),
+            ..Default::default()
+        })
+    }
+
+    async fn tag_resource(&self, request: TagResourceRequest) -> Result<(), anyhow::Error> {
+        log::debug!("AddTagsToResource {}", request.secret_id);
+        let tags = request
+            .tags
+            .into_iter()
+            .map(|t| rusoto_ssm::Tag {
+                key: t.key.unwrap_or_default(),
+                value: t.value.unwrap_or_default(),
+            })
+            .collect();
+        self.client
+            .add_tags_to_resource(AddTagsToResourceRequest {
+                resource_id: request.secret_id,
+                resource_type: "Parameter".to_string(),
+                tags,
+            })
+            .await
+            .map_err(|e| {
+                convert(e, |e| match e {
+                    AddTagsToResourceError::InvalidResourceId(m) => {
+                        Ok(TagResourceError::ResourceNotFound(m))
+                    }
+                    AddTagsToResourceError::InternalServerError(m) => {
+                        Ok(TagResourceError::InternalServiceError(m))
+                    }
+                    e => Err(e),
+                })
+            })?;
+        Ok(())
+    }
+
+    /// Parameters can't be replicated, so only their tags are described.
+    async fn describe_secret(
+        &self,
+        request: DescribeSecretRequest,
+    ) -> Result<DescribeSecretResponse, anyhow::Error> {
+        log::debug!("ListTagsForResource {}", request.secret_id);
+        let response = self
+            .client
+            .list_tags_for_resource(ListTagsForResourceRequest {
+                resource_id: request.secret_id.clone(),
+                resource_type: "Parameter".to_string(),
+            })
+            .await
+            .map_err(|e| {
+                convert(e, |e| match e {
+                    ListTagsForResourceError::InvalidResourceId(m) => {
+                        Ok(DescribeSecretError::ResourceNotFound(m))
+                    }
+                    ListTagsForResourceError::InternalServerError(m) => {
+                        Ok(DescribeSecretError::InternalServiceError(m))
+                    }
+                    e => Err(e),
+                })
+            })?;
+        let tags = response
+            .tag_list
+            .unwrap_or_default()
+            .into_iter()
+            .map(|t| Tag {
+                key: Some(t.key),
+                value: Some(t.value),
+            })
+            .collect();
+        Ok(DescribeSecretResponse {
+            name: Some(request.secret_id),
+            tags: Some(tags),
+            ..Default::default()
+        })
+    }
+
+    fn size_limit(&self) -> usize {
+        PARAMETER_SIZE_LIMIT
+    }
+}
+
+/// Picks the value to store in a parameter, which can only hold text.
+fn plain_value(secret_string: Option<String>, binary: bool) -> Result<String, anyhow::Error> {
+    match secret_string {
+        Some(value) if !binary => Ok(value),
+        _ => anyhow::bail!(
+            "The ssm backend can only store plain JSON; don't use --binary or --compress"
+        ),
+    }
+}
+
+/// Converts an error from Parameter Store into the equivalent Secrets Manager error where
+/// `service` finds one, keeping the original otherwise.
+fn convert<E, F>(e: RusotoError<E>, service: impl FnOnce(E) -> Result<F, E>) -> anyhow::Error
+where
+    E: std::error::Error + Send + Sync + 'static,
+    F: std::error::Error + Send + Sync + 'static,
+{
+    let converted: RusotoError<F> = match e {
+        RusotoError::Service(e) => match service(e) {
+            Ok(e) => RusotoError::Service(e),
+            Err(e) => return RusotoError::Service(e).into(),
+        },
+        RusotoError::HttpDispatch(e) => RusotoError::HttpDispatch(e),
+        RusotoError::Credentials(e) => RusotoError::Credentials(e),
+        RusotoError::Validation(m) => RusotoError::Validation(m),
+        RusotoError::ParseError(m) => RusotoError::ParseError(m),
+        RusotoError::Unknown(response) => RusotoError::Unknown(response),
+        RusotoError::Blocking => RusotoError::Blocking,
+    };
+    converted.into()
+}
@@ -0,0 +1,141 @@
+//! Time limits on requests.
+
+use std::future::Future;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use rusoto_secretsmanager::{
+    CreateSecretRequest, CreateSecretResponse, DescribeSecretRequest, DescribeSecretResponse,
+    GetSecretValueRequest, GetSecretValueResponse, ListSecretVersionIdsRequest,
+    ListSecretVersionIdsResponse, ListSecretsRequest, ListSecretsResponse, PutSecretValueRequest,
+    PutSecretValueResponse, ReplicateSecretToRegionsRequest, ReplicateSecretToRegionsResponse,
+    TagResourceRequest, UpdateSecretRequest, UpdateSecretResponse, UpdateSecretVersionStageRequest,
+    UpdateSecretVersionStageResponse,
+};
+
+use crate::backend::SecretsBackend;
+use crate::exit::Failure;
+
+/// A backend that fails any request taking longer than a fixed duration, so that a hung
+/// connection can't block forever.
+#[derive(Debug)]
+pub struct Timeout<B> {
+    inner: B,
+    duration: Duration,
+}
+
+impl<B: SecretsBackend> Timeout<B> {
+    /// Wraps `inner`, failing each call that takes longer than `duration`.
+    pub fn new(inner: B, duration: Duration) -> Self {
+        Timeout { inner, duration }
+    }
+
+    /// Waits for `call` to finish, failing if it takes longer than the timeout.
+    async fn limit<T>(
+        &self,
+        operation: &str,
+        call: impl Future<Output = Result<T, anyhow::Error>>,
+    ) -> Result<T, anyhow::Error> {
+        match tokio::time::timeout(self.duration, call).await {
+            Ok(result) => result,
+            Err(_) => Err(Failure::Network.error(format!(
+                "{} request timed out after {}s",
+                operation,
+                self.duration.as_secs_f64()
+            ))),
+        }
+    }
+}
+
+#[async_trait]
+impl<B: SecretsBackend> SecretsBackend for Timeout<B> {
+    async fn get_secret_value(
+        &self,
+        request: GetSecretValueRequest,
+    ) -> Result<GetSecretValueResponse, anyhow::Error> {
+        self.limit("GetSecretValue", self.inner.get_secret_value(request))
+            .await
+    }
+
+    async fn put_secret_value(
+        &self,
+        request: PutSecretValueRequest,
+    ) -> Result<PutSecretValueResponse, anyhow::Error> {
+        self.limit("PutSecretValue", self.inner.put_secret_value(request))
+            .await
+    }
+
+    async fn list_secret_version_ids(
+        &self,
+        request: ListSecretVersionIdsRequest,
+    ) -> Result<ListSecretVersionIdsResponse, anyhow::Error> {
+        self.limit(
+            "ListSecretVersionIds",
+            self.inner.list_secret_version_ids(request),
+        )
+        .await
+    }
+
+    async fn create_secret(
+        &self,
+        request: CreateSecretRequest,
+    ) -> Result<CreateSecretResponse, anyhow::Error> {
+        self.limit("CreateSecret", self.inner.create_secret(request))
+            .await
+    }
+
+    async fn update_secret(
+        &self,
+        request: UpdateSecretRequest,
+    ) -> Result<UpdateSecretResponse, anyhow::Error> {
+        self.limit("UpdateSecret", self.inner.update_secret(request))
+            .await
+    }
+
+    async fn tag_resource(&self, request: TagResourceRequest) -> Result<(), anyhow::Error> {
+        self.limit("TagResource", self.inner.tag_resource(request))
+            .await
+    }
+
+    async fn update_secret_version_stage(
+        &self,
+        request: UpdateSecretVersionStageRequest,
+    ) -> Result<UpdateSecretVersionStageResponse, anyhow::Error> {
+        self.limit(
+            "UpdateSecretVersionStage",
+            self.inner.update_secret_version_stage(request),
+        )
+        .await
+    }
+
+    async fn describe_secret(
+        &self,
+        request: DescribeSecretRequest,
+    ) -> Result<DescribeSecretResponse, anyhow::Error> {
+        self.limit("DescribeSecret", self.inner.describe_secret(request))
+            .await
+    }
+
+    async fn replicate_secret_to_regions(
+        &self,
+        request: ReplicateSecretToRegionsRequest,
+    ) -> Result<ReplicateSecretToRegionsResponse, anyhow::Error> {
+        self.limit(
+            "ReplicateSecretToRegions",
+            self.inner.replicate_secret_to_regions(request),
+        )
+        .await
+    }
+
+    async fn list_secrets(
+        &self,
+        request: ListSecretsRequest,
+    ) -> Result<ListSecretsResponse, anyhow::Error> {
+        self.limit("ListSecrets", self.inner.list_secrets(request))
+            .await
+    }
+
+    fn size_limit(&self) -> usize {
+        self.inner.size_limit()
+    }
+}
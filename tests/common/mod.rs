@@ -0,0 +1,40 @@
+//! Helpers shared by the tests that get keys.
+
+use std::fs;
+use std::path::PathBuf;
+
+use ssh_keys::GetOpt;
+use uuid::Uuid;
+
+/// A new empty directory under the system's temporary directory.
+pub fn temp_dir() -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("ssh-keys-test-{}", Uuid::new_v4()));
+    fs::create_dir(&dir).unwrap();
+    dir
+}
+
+/// Options for getting every key into `outdir`, as `ssh-keys get outdir` would but writing
+/// four files at a time.
+pub fn get_opt(outdir: PathBuf) -> GetOpt {
+    GetOpt {
+        outdir: Some(outdir),
+        to_tar: None,
+        authorized_keys: None,
+        merge: false,
+        force: false,
+        merge_known_hosts: false,
+        ssh_dir: false,
+        checksums: false,
+        preserve_mtime: false,
+        version_id: None,
+        version_stage: None,
+        only: Vec::new(),
+        select: false,
+        prefix: None,
+        pub_mode: None,
+        private_mode: None,
+        restore_comments: false,
+        passphrase: None,
+        concurrency: 4,
+    }
+}
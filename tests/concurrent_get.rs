@@ -0,0 +1,133 @@
+mod common;
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use ssh_keys::{get, GetOpt, LocalBackend, OutputFormat};
+
+use common::temp_dir;
+
+/// Options for getting every key into `outdir` along with a checksum manifest.
+fn get_opt(outdir: PathBuf) -> GetOpt {
+    GetOpt {
+        checksums: true,
+        ..common::get_opt(outdir)
+    }
+}
+
+/// Names in `dir`, or below it, of the temporary files and directories left by a get.
+fn leftovers(dir: &Path) -> Vec<PathBuf> {
+    let mut found = Vec::new();
+    for entry in fs::read_dir(dir).unwrap() {
+        let path = entry.unwrap().path();
+        if path
+            .file_name()
+            .unwrap()
+            .to_string_lossy()
+            .starts_with(".ssh-keys-")
+        {
+            found.push(path.clone());
+        }
+        if path.is_dir() {
+            found.extend(leftovers(&path));
+        }
+    }
+    found
+}
+
+fn backend(root: &Path, secret: &str) -> LocalBackend {
+    let store = root.join("store");
+    fs::create_dir_all(&store).unwrap();
+    fs::write(store.join("keys.json"), secret).unwrap();
+    LocalBackend::new(store)
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn concurrent_gets_into_sibling_dirs_both_succeed() {
+    let root = temp_dir();
+    let backend = backend(
+        &root,
+        r#"{"id_ed25519": "private\n", "id_ed25519.pub": "ssh-ed25519 AAAA\n", "config.d/host": "Host *\n"}"#,
+    );
+    let (first, second) = (root.join("first"), root.join("second"));
+
+    let (a, b) = tokio::join!(
+        get(
+            &backend,
+            "keys".to_string(),
+            get_opt(first.clone()),
+            OutputFormat::Text,
+        ),
+        get(
+            &backend,
+            "keys".to_string(),
+            get_opt(second.clone()),
+            OutputFormat::Text,
+        ),
+    );
+
+    a.unwrap();
+    b.unwrap();
+    for outdir in &[&first, &second] {
+        assert_eq!(fs::read(outdir.join("id_ed25519")).unwrap(), b"private\n");
+        assert_eq!(fs::read(outdir.join("config.d/host")).unwrap(), b"Host *\n");
+        assert!(outdir.join("SHA256SUMS").is_file());
+    }
+    assert!(leftovers(&root).is_empty(), "{:?}", leftovers(&root));
+    fs::remove_dir_all(&root).unwrap();
+}
+
+#[tokio::test]
+async fn failed_get_removes_its_temporary_files() {
+    let root = temp_dir();
+    // A key can't be both a file and the directory holding another key.
+    let backend = backend(&root, r#"{"a": "file\n", "a/b": "nested\n"}"#);
+    let outdir = root.join("out");
+    fs::create_dir(&outdir).unwrap();
+
+    let result = get(
+        &backend,
+        "keys".to_string(),
+        get_opt(outdir.clone()),
+        OutputFormat::Text,
+    )
+    .await;
+
+    assert!(result.is_err());
+    assert!(leftovers(&root).is_empty(), "{:?}", leftovers(&root));
+    assert_eq!(fs::read_dir(&outdir).unwrap().count(), 0);
+    fs::remove_dir_all(&root).unwrap();
+}
+
+#[tokio::test]
+async fn failed_move_into_outdir_restores_it() {
+    let root = temp_dir();
+    // The key b can be written to the staging directory, but not moved over the directory b.
+    let backend = backend(
+        &root,
+        r#"{"0/new": "nested\n", "a": "new\n", "b": "new\n", "c": "new\n"}"#,
+    );
+    let outdir = root.join("out");
+    fs::create_dir_all(outdir.join("b")).unwrap();
+    fs::write(outdir.join("b/kept"), "kept\n").unwrap();
+    fs::write(outdir.join("a"), "old\n").unwrap();
+
+    let result = get(
+        &backend,
+        "keys".to_string(),
+        GetOpt {
+            force: true,
+            ..get_opt(outdir.clone())
+        },
+        OutputFormat::Text,
+    )
+    .await;
+
+    assert!(result.is_err());
+    assert_eq!(fs::read(outdir.join("a")).unwrap(), b"old\n");
+    assert_eq!(fs::read(outdir.join("b/kept")).unwrap(), b"kept\n");
+    assert!(!outdir.join("0").exists());
+    assert!(!outdir.join("c").exists());
+    assert!(leftovers(&root).is_empty(), "{:?}", leftovers(&root));
+    fs::remove_dir_all(&root).unwrap();
+}
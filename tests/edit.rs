@@ -0,0 +1,40 @@
+use std::env;
+use std::fs;
+
+use ssh_keys::{edit, encode, Entry, Files, LocalBackend, Payload, Storage, SECRET_SIZE_LIMIT};
+use uuid::Uuid;
+
+#[tokio::test]
+async fn edit_without_changes_uploads_nothing() {
+    let dir = std::env::temp_dir().join(format!("ssh-keys-test-{}", Uuid::new_v4()));
+    fs::create_dir(&dir).unwrap();
+    // Enough keys for a random map order to differ between two serializations.
+    let files = (0..16)
+        .map(|i| {
+            let entry = Entry {
+                contents: format!("key {}\n", i).into_bytes(),
+                mode: Some(0o600),
+                mtime: None,
+                comments: None,
+                encrypted: false,
+            };
+            (format!("key_{}", i), entry)
+        })
+        .collect::<Files>();
+    let payload = encode(&files, Storage::String, false, SECRET_SIZE_LIMIT).unwrap();
+    let contents = match payload {
+        Payload::Text(s) => s,
+        Payload::Binary(_) => unreachable!(),
+    };
+    fs::write(dir.join("keys.json"), &contents).unwrap();
+    let backend = LocalBackend::new(dir.clone());
+    // An editor that leaves the file as it is.
+    env::set_var("EDITOR", "true");
+
+    edit(&backend, "keys".to_string(), false, false)
+        .await
+        .unwrap();
+
+    assert_eq!(fs::read_to_string(dir.join("keys.json")).unwrap(), contents);
+    fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,98 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use ssh_keys::{decode, put, sync, verify, Files, LocalBackend, OutputFormat, PutOpt, ScanOpt};
+use structopt::StructOpt as _;
+use uuid::Uuid;
+
+const PASSPHRASE: &str = "correct-horse";
+
+/// A local store holding the secret `keys`, put from a directory of two files with
+/// `--passphrase`, along with that directory.
+async fn encrypted_store() -> (PathBuf, PathBuf, LocalBackend) {
+    let root = std::env::temp_dir().join(format!("ssh-keys-test-{}", Uuid::new_v4()));
+    let (indir, store) = (root.join("keys"), root.join("store"));
+    fs::create_dir_all(&indir).unwrap();
+    fs::create_dir(&store).unwrap();
+    fs::write(indir.join("config"), "Host *\n").unwrap();
+    fs::write(indir.join("known_hosts"), "example.com ssh-ed25519 AAAA\n").unwrap();
+    fs::write(store.join("keys.json"), "{}").unwrap();
+    let backend = LocalBackend::new(store);
+    let passphrase = format!("--passphrase={}", PASSPHRASE);
+    let opt = PutOpt::from_iter(&["put", &passphrase, indir.to_str().unwrap()]);
+    put(
+        &backend,
+        "keys".to_string(),
+        true,
+        false,
+        opt,
+        OutputFormat::Text,
+    )
+    .await
+    .unwrap();
+    (root, indir, backend)
+}
+
+/// The files stored as `keys`.
+fn stored(root: &Path) -> Files {
+    let stored = fs::read_to_string(root.join("store").join("keys.json")).unwrap();
+    decode(Some(stored), None).unwrap()
+}
+
+#[tokio::test]
+async fn sync_needs_the_passphrase_of_encrypted_keys() {
+    let (root, indir, backend) = encrypted_store().await;
+    let scan_opt = ScanOpt::default();
+
+    let result = sync(
+        &backend,
+        "keys".to_string(),
+        &indir,
+        &scan_opt,
+        None,
+        true,
+        true,
+    )
+    .await;
+
+    let error = result.unwrap_err().to_string();
+    assert!(error.contains("pass --passphrase"), "{}", error);
+    fs::remove_dir_all(&root).unwrap();
+}
+
+#[tokio::test]
+async fn sync_keeps_keys_encrypted() {
+    let (root, indir, backend) = encrypted_store().await;
+    let scan_opt = ScanOpt::default();
+    let before = stored(&root);
+    fs::write(indir.join("notes"), "notes\n").unwrap();
+
+    sync(
+        &backend,
+        "keys".to_string(),
+        &indir,
+        &scan_opt,
+        Some(Some(PASSPHRASE.to_string())),
+        true,
+        false,
+    )
+    .await
+    .unwrap();
+
+    let after = stored(&root);
+    assert_eq!(after.len(), 3);
+    assert!(after.values().all(|entry| entry.encrypted));
+    // Unchanged keys keep their ciphertext.
+    assert_eq!(after["config"], before["config"]);
+    let matches = verify(
+        &backend,
+        "keys".to_string(),
+        &indir,
+        &scan_opt,
+        Some(Some(PASSPHRASE.to_string())),
+    )
+    .await
+    .unwrap();
+    assert!(matches);
+    fs::remove_dir_all(&root).unwrap();
+}
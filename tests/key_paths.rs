@@ -0,0 +1,90 @@
+mod common;
+
+use std::fs;
+
+use ssh_keys::{get, validate_key_path, GetOpt, LocalBackend, OutputFormat};
+
+use common::{get_opt, temp_dir};
+
+#[test]
+fn accepts_relative_paths() {
+    for name in &[
+        "id_ed25519",
+        "id_ed25519.pub",
+        "config.d/host1",
+        "a/b/c",
+        "..rc",
+    ] {
+        assert!(validate_key_path(name).is_ok(), "{}", name);
+    }
+}
+
+#[test]
+fn rejects_parent_components() {
+    for name in &[
+        "..",
+        "../id_ed25519",
+        "../../etc/authorized_keys",
+        "a/../../b",
+        "a/..",
+    ] {
+        assert!(validate_key_path(name).is_err(), "{}", name);
+    }
+}
+
+#[test]
+fn rejects_absolute_paths() {
+    for name in &["/", "/etc/passwd", "//etc/passwd"] {
+        assert!(validate_key_path(name).is_err(), "{}", name);
+    }
+}
+
+#[test]
+fn rejects_windows_separators() {
+    for name in &[
+        "..\\id_ed25519",
+        "a\\..\\..\\b",
+        "C:\\Users\\id_ed25519",
+        "\\\\host\\share",
+    ] {
+        assert!(validate_key_path(name).is_err(), "{}", name);
+    }
+}
+
+#[test]
+fn rejects_empty_and_current_components() {
+    for name in &["", ".", "./id_ed25519", "a//b", "a/"] {
+        assert!(validate_key_path(name).is_err(), "{}", name);
+    }
+}
+
+#[tokio::test]
+async fn get_refuses_to_write_outside_outdir() {
+    let root = temp_dir();
+    let store = root.join("store");
+    fs::create_dir(&store).unwrap();
+    fs::write(
+        store.join("keys.json"),
+        r#"{"id_ed25519.pub": "ssh-ed25519 AAAA\n", "../escaped": "oops\n"}"#,
+    )
+    .unwrap();
+    let outdir = root.join("out");
+    let backend = LocalBackend::new(store);
+
+    let result = get(
+        &backend,
+        "keys".to_string(),
+        GetOpt {
+            concurrency: 1,
+            ..get_opt(outdir.clone())
+        },
+        OutputFormat::Text,
+    )
+    .await;
+
+    let message = format!("{:#}", result.unwrap_err());
+    assert!(message.contains("../escaped"), "{}", message);
+    assert!(!root.join("escaped").exists());
+    assert!(!outdir.exists());
+    fs::remove_dir_all(&root).unwrap();
+}
@@ -0,0 +1,126 @@
+use std::fs;
+use std::sync::Mutex;
+
+use async_trait::async_trait;
+use rusoto_secretsmanager::{
+    GetSecretValueRequest, GetSecretValueResponse, ListSecretsRequest, ListSecretsResponse,
+    PutSecretValueRequest, PutSecretValueResponse, SecretListEntry,
+};
+use ssh_keys::{list_secret_ids, LocalBackend, SecretsBackend};
+use uuid::Uuid;
+
+/// Names the fake lists, in pages of three, as Secrets Manager would for the filter
+/// `name: hosts/`; it matches regardless of case.
+const NAMES: &[&str] = &[
+    "hosts/web",
+    "HOSTS/legacy",
+    "hosts/db",
+    "hosts/app",
+    "hosts/web",
+    "hosts/cache",
+    "hosts/bastion",
+];
+const PAGE_SIZE: usize = 3;
+
+/// A backend listing `NAMES`, which records the token and filters of each request.
+#[derive(Default)]
+struct Fake {
+    requested: Mutex<Vec<(Option<String>, Vec<String>)>>,
+}
+
+#[async_trait]
+impl SecretsBackend for Fake {
+    async fn get_secret_value(
+        &self,
+        _request: GetSecretValueRequest,
+    ) -> Result<GetSecretValueResponse, anyhow::Error> {
+        Err(anyhow::anyhow!("Only listing is faked"))
+    }
+
+    async fn put_secret_value(
+        &self,
+        _request: PutSecretValueRequest,
+    ) -> Result<PutSecretValueResponse, anyhow::Error> {
+        Err(anyhow::anyhow!("Only listing is faked"))
+    }
+
+    async fn list_secrets(
+        &self,
+        request: ListSecretsRequest,
+    ) -> Result<ListSecretsResponse, anyhow::Error> {
+        let values = request
+            .filters
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|filter| filter.key.as_deref() == Some("name"))
+            .flat_map(|filter| filter.values.unwrap_or_default())
+            .collect();
+        self.requested
+            .lock()
+            .unwrap()
+            .push((request.next_token.clone(), values));
+        let start = request
+            .next_token
+            .as_deref()
+            .map_or(0, |token| token.parse::<usize>().unwrap());
+        let end = NAMES.len().min(start + PAGE_SIZE);
+        let secrets = NAMES[start..end]
+            .iter()
+            .map(|name| SecretListEntry {
+                name: Some(name.to_string()),
+                ..Default::default()
+            })
+            .collect();
+        Ok(ListSecretsResponse {
+            secret_list: Some(secrets),
+            next_token: if end < NAMES.len() {
+                Some(end.to_string())
+            } else {
+                None
+            },
+        })
+    }
+}
+
+#[tokio::test]
+async fn lists_every_page_of_matching_secrets() {
+    let backend = Fake::default();
+
+    let secret_ids = list_secret_ids(&backend, "hosts/").await.unwrap();
+
+    assert_eq!(
+        secret_ids,
+        vec![
+            "hosts/app",
+            "hosts/bastion",
+            "hosts/cache",
+            "hosts/db",
+            "hosts/web"
+        ]
+    );
+    let filter = vec!["hosts/".to_string()];
+    assert_eq!(
+        *backend.requested.lock().unwrap(),
+        vec![
+            (None, filter.clone()),
+            (Some("3".to_string()), filter.clone()),
+            (Some("6".to_string()), filter),
+        ]
+    );
+}
+
+#[tokio::test]
+async fn lists_matching_local_secrets() {
+    let dir = std::env::temp_dir().join(format!("ssh-keys-test-{}", Uuid::new_v4()));
+    fs::create_dir(&dir).unwrap();
+    for name in &["team-a", "team-b", "other", ".team-hidden"] {
+        fs::write(dir.join(format!("{}.json", name)), "{}").unwrap();
+    }
+    fs::write(dir.join("team-notes.txt"), "").unwrap();
+    let backend = LocalBackend::new(dir.clone());
+
+    let secret_ids = list_secret_ids(&backend, "team-").await.unwrap();
+
+    assert_eq!(secret_ids, vec!["team-a", "team-b"]);
+    fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,53 @@
+use std::fs;
+use std::path::Path;
+
+use ssh_keys::{classify, put, Failure, LocalBackend, OutputFormat, PutOpt};
+use structopt::StructOpt as _;
+use uuid::Uuid;
+
+/// Puts `indir` into the secret `keys`, with `--only-if-newer` if `only_if_newer`.
+async fn put_keys(
+    backend: &LocalBackend,
+    indir: &Path,
+    only_if_newer: bool,
+) -> Result<(), anyhow::Error> {
+    let mut args = vec!["put", "--force", indir.to_str().unwrap()];
+    if only_if_newer {
+        args.insert(1, "--only-if-newer");
+    }
+    put(
+        backend,
+        "keys".to_string(),
+        true,
+        false,
+        PutOpt::from_iter(&args),
+        OutputFormat::Text,
+    )
+    .await
+}
+
+#[tokio::test]
+async fn only_if_newer_compares_with_the_recorded_put() {
+    let root = std::env::temp_dir().join(format!("ssh-keys-test-{}", Uuid::new_v4()));
+    let (indir, store) = (root.join("keys"), root.join("store"));
+    fs::create_dir_all(&indir).unwrap();
+    fs::create_dir(&store).unwrap();
+    fs::write(indir.join("config"), "Host *\n").unwrap();
+    fs::write(store.join("keys.json"), "{}").unwrap();
+    let backend = LocalBackend::new(store.clone());
+    let tags = store.join(".keys.tags.json");
+
+    // Nothing has been recorded yet, so there is nothing to refuse.
+    put_keys(&backend, &indir, true).await.unwrap();
+    let recorded = fs::read_to_string(&tags).unwrap();
+    assert!(recorded.contains("ssh-keys:updated-at"), "{}", recorded);
+
+    fs::write(&tags, r#"{"ssh-keys:updated-at": "2999-01-01T00:00:00Z"}"#).unwrap();
+    let e = put_keys(&backend, &indir, true).await.unwrap_err();
+    assert_eq!(classify(&e), Some(Failure::Conflict), "{:#}", e);
+
+    fs::write(&tags, r#"{"ssh-keys:updated-at": "2000-01-01T00:00:00Z"}"#).unwrap();
+    put_keys(&backend, &indir, true).await.unwrap();
+    assert!(!fs::read_to_string(&tags).unwrap().contains("2000-01-01"));
+    fs::remove_dir_all(&root).unwrap();
+}
@@ -0,0 +1,162 @@
+use std::sync::{Arc, Mutex};
+
+use async_trait::async_trait;
+use bytes::Bytes;
+use http::{HeaderMap, StatusCode};
+use rusoto_core::request::BufferedHttpResponse;
+use rusoto_core::RusotoError;
+use rusoto_secretsmanager::{
+    GetSecretValueRequest, GetSecretValueResponse, ListSecretVersionIdsError,
+    ListSecretVersionIdsRequest, ListSecretVersionIdsResponse, PutSecretValueRequest,
+    PutSecretValueResponse, SecretVersionsListEntry,
+};
+use ssh_keys::{list_versions, Retry, SecretsBackend};
+
+const VERSIONS: usize = 10;
+const PAGE_SIZE: usize = 3;
+
+/// A backend listing `VERSIONS` versions in pages of `PAGE_SIZE`, which throttles the first
+/// `throttles` requests for the page starting at `throttled_page`.
+struct Fake {
+    throttled_page: usize,
+    throttles: Mutex<u32>,
+    requested: Arc<Mutex<Vec<Option<String>>>>,
+}
+
+impl Fake {
+    fn new(throttled_page: usize, throttles: u32) -> Self {
+        Fake {
+            throttled_page,
+            throttles: Mutex::new(throttles),
+            requested: Arc::new(Mutex::new(Vec::new())),
+        }
+    }
+}
+
+fn throttled() -> anyhow::Error {
+    RusotoError::<ListSecretVersionIdsError>::Unknown(BufferedHttpResponse {
+        status: StatusCode::BAD_REQUEST,
+        body: Bytes::from_static(b"{\"__type\":\"ThrottlingException\"}"),
+        headers: HeaderMap::default(),
+    })
+    .into()
+}
+
+#[async_trait]
+impl SecretsBackend for Fake {
+    async fn get_secret_value(
+        &self,
+        _request: GetSecretValueRequest,
+    ) -> Result<GetSecretValueResponse, anyhow::Error> {
+        Err(anyhow::anyhow!("Only listing is faked"))
+    }
+
+    async fn put_secret_value(
+        &self,
+        _request: PutSecretValueRequest,
+    ) -> Result<PutSecretValueResponse, anyhow::Error> {
+        Err(anyhow::anyhow!("Only listing is faked"))
+    }
+
+    async fn list_secret_version_ids(
+        &self,
+        request: ListSecretVersionIdsRequest,
+    ) -> Result<ListSecretVersionIdsResponse, anyhow::Error> {
+        self.requested
+            .lock()
+            .unwrap()
+            .push(request.next_token.clone());
+        let start = request
+            .next_token
+            .as_deref()
+            .map_or(0, |token| token.parse::<usize>().unwrap());
+        if start == self.throttled_page {
+            let mut throttles = self.throttles.lock().unwrap();
+            if *throttles > 0 {
+                *throttles -= 1;
+                return Err(throttled());
+            }
+        }
+        let end = VERSIONS.min(start + PAGE_SIZE);
+        let versions = (start..end)
+            .map(|i| SecretVersionsListEntry {
+                created_date: Some(i as f64),
+                version_id: Some(format!("v{}", i)),
+                ..Default::default()
+            })
+            .collect();
+        Ok(ListSecretVersionIdsResponse {
+            versions: Some(versions),
+            next_token: if end < VERSIONS {
+                Some(end.to_string())
+            } else {
+                None
+            },
+            ..Default::default()
+        })
+    }
+}
+
+fn version_ids(entries: &[SecretVersionsListEntry]) -> Vec<String> {
+    entries
+        .iter()
+        .map(|entry| entry.version_id.clone().unwrap())
+        .collect()
+}
+
+fn tokens(tokens: &[Option<&str>]) -> Vec<Option<String>> {
+    tokens.iter().map(|t| t.map(str::to_string)).collect()
+}
+
+#[tokio::test]
+async fn lists_every_page() {
+    let backend = Fake::new(0, 0);
+
+    let entries = list_versions(&backend, "keys".to_string()).await.unwrap();
+
+    let expected = (0..VERSIONS)
+        .rev()
+        .map(|i| format!("v{}", i))
+        .collect::<Vec<_>>();
+    assert_eq!(version_ids(&entries), expected);
+    assert_eq!(
+        *backend.requested.lock().unwrap(),
+        tokens(&[None, Some("3"), Some("6"), Some("9")])
+    );
+}
+
+#[tokio::test]
+async fn resumes_from_the_throttled_page() {
+    let backend = Retry::new(Fake::new(6, 2), 3);
+
+    let entries = list_versions(&backend, "keys".to_string()).await.unwrap();
+
+    let expected = (0..VERSIONS)
+        .rev()
+        .map(|i| format!("v{}", i))
+        .collect::<Vec<_>>();
+    assert_eq!(version_ids(&entries), expected);
+}
+
+#[tokio::test]
+async fn retries_only_the_throttled_page() {
+    let fake = Fake::new(3, 1);
+    let requested = Arc::clone(&fake.requested);
+    let backend = Retry::new(fake, 3);
+
+    list_versions(&backend, "keys".to_string()).await.unwrap();
+
+    assert_eq!(
+        *requested.lock().unwrap(),
+        tokens(&[None, Some("3"), Some("3"), Some("6"), Some("9")])
+    );
+}
+
+#[tokio::test]
+async fn gives_up_after_max_retries() {
+    let backend = Retry::new(Fake::new(3, 5), 2);
+
+    let result = list_versions(&backend, "keys".to_string()).await;
+
+    assert!(result.is_err());
+}
@@ -0,0 +1,72 @@
+//! Checks of legacy PEM RSA keys against their public keys. The fixtures were made with
+//! `openssl genrsa -traditional -aes128` and converted with `openssl rsa -traditional`; their
+//! passphrase is `correct-horse`.
+
+use ssh_keys::{is_encrypted_pem_key, key_pair_matches};
+
+const PASSPHRASE: &str = "correct-horse";
+const RSA: &[u8] = include_bytes!("fixtures/rsa.pem");
+const RSA_AES128: &[u8] = include_bytes!("fixtures/rsa_aes128.pem");
+const RSA_DES3: &[u8] = include_bytes!("fixtures/rsa_des3.pem");
+const RSA_PUB: &[u8] = include_bytes!("fixtures/rsa.pub");
+const OTHER_PUB: &[u8] = include_bytes!("fixtures/other.pub");
+
+/// `pem` with its base64 body replaced by that of `der`.
+fn with_body(pem: &[u8], der: &[u8]) -> Vec<u8> {
+    let pem = std::str::from_utf8(pem).unwrap();
+    let begin = pem.lines().next().unwrap();
+    let end = pem.lines().last().unwrap();
+    format!("{}\n{}\n{}\n", begin, base64::encode(der), end).into_bytes()
+}
+
+/// The DER body of an unencrypted PEM key.
+fn body(pem: &[u8]) -> Vec<u8> {
+    let pem = std::str::from_utf8(pem).unwrap();
+    let lines = pem.lines().collect::<Vec<_>>();
+    base64::decode(lines[1..lines.len() - 1].concat()).unwrap()
+}
+
+#[test]
+fn matches_an_unencrypted_key() {
+    assert!(!is_encrypted_pem_key(RSA));
+    assert_eq!(key_pair_matches(RSA, RSA_PUB, None), Some(true));
+    assert_eq!(key_pair_matches(RSA, OTHER_PUB, None), Some(false));
+}
+
+#[test]
+fn matches_encrypted_keys_with_their_passphrase() {
+    for key in &[RSA_AES128, RSA_DES3] {
+        assert!(is_encrypted_pem_key(key));
+        assert_eq!(key_pair_matches(key, RSA_PUB, Some(PASSPHRASE)), Some(true));
+        assert_eq!(
+            key_pair_matches(key, OTHER_PUB, Some(PASSPHRASE)),
+            Some(false)
+        );
+    }
+}
+
+#[test]
+fn cannot_check_encrypted_keys_without_the_right_passphrase() {
+    for key in &[RSA_AES128, RSA_DES3] {
+        assert_eq!(key_pair_matches(key, RSA_PUB, None), None);
+        assert_eq!(key_pair_matches(key, RSA_PUB, Some("wrong")), None);
+    }
+}
+
+#[test]
+fn cannot_check_truncated_or_invalid_der() {
+    let der = body(RSA);
+    for len in &[0, 1, 4, der.len() / 2, der.len() - 1] {
+        let truncated = with_body(RSA, &der[..*len]);
+        assert_eq!(key_pair_matches(&truncated, RSA_PUB, None), None);
+    }
+    // A sequence claiming to be longer than the whole key.
+    let mut oversized = der.clone();
+    oversized[2] = 0xff;
+    assert_eq!(
+        key_pair_matches(&with_body(RSA, &oversized), RSA_PUB, None),
+        None
+    );
+    let garbage = with_body(RSA, b"not a DER encoded key at all");
+    assert_eq!(key_pair_matches(&garbage, RSA_PUB, None), None);
+}
@@ -0,0 +1,43 @@
+use std::fs;
+
+use ssh_keys::{put_one, LocalBackend};
+use uuid::Uuid;
+
+#[tokio::test]
+async fn put_one_checks_before_reading_stdin() {
+    let dir = std::env::temp_dir().join(format!("ssh-keys-test-{}", Uuid::new_v4()));
+    fs::create_dir(&dir).unwrap();
+    fs::write(dir.join("keys.json"), "{}").unwrap();
+    let backend = LocalBackend::new(dir.clone());
+
+    // Neither of these gets as far as reading the key, so the test doesn't wait on stdin.
+    let unconfirmed = put_one(
+        &backend,
+        "keys".to_string(),
+        "id_x".to_string(),
+        false,
+        false,
+    )
+    .await
+    .unwrap_err()
+    .to_string();
+    assert!(unconfirmed.contains("pass --yes"), "{}", unconfirmed);
+    let outside = put_one(
+        &backend,
+        "keys".to_string(),
+        "../x".to_string(),
+        true,
+        false,
+    )
+    .await
+    .unwrap_err()
+    .to_string();
+    assert!(
+        outside.contains("outside the output directory"),
+        "{}",
+        outside
+    );
+
+    assert_eq!(fs::read_to_string(dir.join("keys.json")).unwrap(), "{}");
+    fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,96 @@
+mod common;
+
+use std::fs;
+
+use ssh_keys::{decode, get, put, rotate, KeyType, LocalBackend, OutputFormat, PutOpt};
+use structopt::StructOpt as _;
+
+use common::{get_opt, temp_dir};
+
+#[tokio::test]
+async fn rotated_keys_can_be_got_and_put_again() {
+    let root = temp_dir();
+    let store = root.join("store");
+    fs::create_dir(&store).unwrap();
+    fs::write(store.join("keys.json"), "{}").unwrap();
+    let backend = LocalBackend::new(store.clone());
+    for _ in 0..2 {
+        rotate(
+            &backend,
+            "keys".to_string(),
+            "id_test".to_string(),
+            KeyType::Ed25519,
+            true,
+            false,
+        )
+        .await
+        .unwrap();
+    }
+    let outdir = root.join("out");
+
+    get(
+        &backend,
+        "keys".to_string(),
+        get_opt(outdir.clone()),
+        OutputFormat::Text,
+    )
+    .await
+    .unwrap();
+    let opt = PutOpt::from_iter(&["put", outdir.to_str().unwrap()]);
+    put(
+        &backend,
+        "keys".to_string(),
+        true,
+        false,
+        opt,
+        OutputFormat::Text,
+    )
+    .await
+    .unwrap();
+
+    let stored = fs::read_to_string(store.join("keys.json")).unwrap();
+    let files = decode(Some(stored), None).unwrap();
+    let mut names = files.keys().map(String::as_str).collect::<Vec<_>>();
+    names.sort_unstable();
+    assert_eq!(
+        names,
+        vec!["id_test", "id_test.old", "id_test.old.pub", "id_test.pub"]
+    );
+    fs::remove_dir_all(&root).unwrap();
+}
+
+#[tokio::test]
+async fn rotated_out_keys_are_left_out_of_authorized_keys() {
+    let root = temp_dir();
+    let store = root.join("store");
+    fs::create_dir(&store).unwrap();
+    fs::write(store.join("keys.json"), "{}").unwrap();
+    let backend = LocalBackend::new(store.clone());
+    for _ in 0..2 {
+        rotate(
+            &backend,
+            "keys".to_string(),
+            "id_test".to_string(),
+            KeyType::Ed25519,
+            true,
+            false,
+        )
+        .await
+        .unwrap();
+    }
+    let path = root.join("authorized_keys");
+    let mut opt = get_opt(root.join("out"));
+    opt.outdir = None;
+    opt.authorized_keys = Some(path.clone());
+
+    get(&backend, "keys".to_string(), opt, OutputFormat::Text)
+        .await
+        .unwrap();
+
+    let stored = fs::read_to_string(store.join("keys.json")).unwrap();
+    let files = decode(Some(stored), None).unwrap();
+    let current = String::from_utf8(files["id_test.pub"].contents.clone()).unwrap();
+    let authorized = fs::read_to_string(&path).unwrap();
+    assert_eq!(authorized.trim(), current.trim());
+    fs::remove_dir_all(&root).unwrap();
+}
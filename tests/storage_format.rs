@@ -0,0 +1,112 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use ssh_keys::{
+    decode_format, delete, encode, rename, Entry, Files, Format, LocalBackend, Payload, Storage,
+    SECRET_SIZE_LIMIT,
+};
+use uuid::Uuid;
+
+fn files() -> Files {
+    ["id_ed25519", "id_ed25519.pub", "config"]
+        .iter()
+        .map(|name| {
+            let entry = Entry {
+                contents: format!("contents of {}\n", name).into_bytes(),
+                mode: Some(0o600),
+                mtime: None,
+                comments: None,
+                encrypted: false,
+            };
+            (name.to_string(), entry)
+        })
+        .collect()
+}
+
+/// A local store holding the secret `keys` as `files`, written with `format`.
+fn store(format: Format) -> (PathBuf, LocalBackend) {
+    let dir = std::env::temp_dir().join(format!("ssh-keys-test-{}", Uuid::new_v4()));
+    fs::create_dir(&dir).unwrap();
+    let payload = encode(&files(), format.storage, format.compact, SECRET_SIZE_LIMIT).unwrap();
+    let contents = match payload {
+        Payload::Text(s) => s.into_bytes(),
+        Payload::Binary(b) => b,
+    };
+    fs::write(dir.join("keys.json"), contents).unwrap();
+    (dir.clone(), LocalBackend::new(dir))
+}
+
+/// The files stored as `keys` and the format they are stored in.
+fn stored(dir: &Path) -> (Files, Format) {
+    let contents = fs::read(dir.join("keys.json")).unwrap();
+    match String::from_utf8(contents) {
+        Ok(s) => decode_format(Some(s), None).unwrap(),
+        Err(e) => decode_format(None, Some(e.as_bytes())).unwrap(),
+    }
+}
+
+#[test]
+fn decode_reports_the_format_written() {
+    for &storage in &[Storage::String, Storage::Binary, Storage::Compressed] {
+        for &compact in &[false, true] {
+            let payload = encode(&files(), storage, compact, SECRET_SIZE_LIMIT).unwrap();
+            let (string, binary) = match payload {
+                Payload::Text(s) => (Some(s), None),
+                Payload::Binary(b) => (None, Some(b)),
+            };
+            let (decoded, format) = decode_format(string, binary.as_deref()).unwrap();
+            assert_eq!(decoded, files());
+            assert_eq!(format, Format { storage, compact });
+        }
+    }
+}
+
+#[tokio::test]
+async fn delete_keeps_a_compressed_secret_compressed() {
+    let format = Format {
+        storage: Storage::Compressed,
+        compact: false,
+    };
+    let (dir, backend) = store(format);
+
+    delete(
+        &backend,
+        "keys".to_string(),
+        "config".to_string(),
+        true,
+        false,
+    )
+    .await
+    .unwrap();
+
+    let (files, stored_format) = stored(&dir);
+    assert!(!files.contains_key("config"));
+    assert_eq!(stored_format, format);
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[tokio::test]
+async fn rename_keeps_compact_json_compact() {
+    let format = Format {
+        storage: Storage::String,
+        compact: true,
+    };
+    let (dir, backend) = store(format);
+
+    rename(
+        &backend,
+        "keys".to_string(),
+        "config".to_string(),
+        "config.old".to_string(),
+        false,
+        true,
+        false,
+    )
+    .await
+    .unwrap();
+
+    let (files, stored_format) = stored(&dir);
+    assert!(files.contains_key("config.old"));
+    assert_eq!(stored_format, format);
+    fs::remove_dir_all(&dir).unwrap();
+}
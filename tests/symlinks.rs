@@ -0,0 +1,67 @@
+use std::fs;
+use std::os::unix::fs::{symlink, PermissionsExt as _};
+use std::path::PathBuf;
+
+use ssh_keys::{scan, ScanOpt};
+use uuid::Uuid;
+
+/// A directory holding a real key, a symlink to it from another directory, and a dangling
+/// symlink and a symlink to a directory next to it.
+fn fixture() -> (PathBuf, PathBuf) {
+    let root = std::env::temp_dir().join(format!("ssh-keys-test-{}", Uuid::new_v4()));
+    let real = root.join("real");
+    let indir = root.join("keys");
+    fs::create_dir_all(&real).unwrap();
+    fs::create_dir_all(&indir).unwrap();
+    fs::write(real.join("id_ed25519"), "private\n").unwrap();
+    fs::set_permissions(real.join("id_ed25519"), fs::Permissions::from_mode(0o600)).unwrap();
+    fs::write(indir.join("config"), "Host *\n").unwrap();
+    symlink(real.join("id_ed25519"), indir.join("id_ed25519")).unwrap();
+    symlink(root.join("missing"), indir.join("dangling")).unwrap();
+    symlink(&real, indir.join("linked_dir")).unwrap();
+    (root, indir)
+}
+
+#[test]
+fn reads_symlinked_files() {
+    let (root, indir) = fixture();
+
+    let files = scan(&indir, &ScanOpt::default()).unwrap();
+
+    let mut keys = files.keys().cloned().collect::<Vec<_>>();
+    keys.sort();
+    assert_eq!(keys, vec!["config", "id_ed25519"]);
+    assert_eq!(files["id_ed25519"].contents, b"private\n");
+    // The mode is the target's, not the link's.
+    assert_eq!(files["id_ed25519"].mode, Some(0o600));
+    fs::remove_dir_all(&root).unwrap();
+}
+
+#[test]
+fn skips_directory_symlinks_when_recursive() {
+    let (root, indir) = fixture();
+    let opt = ScanOpt {
+        recursive: true,
+        ..ScanOpt::default()
+    };
+
+    let files = scan(&indir, &opt).unwrap();
+
+    assert!(files.keys().all(|k| !k.starts_with("linked_dir")));
+    assert!(files.contains_key("id_ed25519"));
+    fs::remove_dir_all(&root).unwrap();
+}
+
+#[test]
+fn skips_symlinks_without_following() {
+    let (root, indir) = fixture();
+    let opt = ScanOpt {
+        no_follow_symlinks: true,
+        ..ScanOpt::default()
+    };
+
+    let files = scan(&indir, &opt).unwrap();
+
+    assert_eq!(files.keys().collect::<Vec<_>>(), vec!["config"]);
+    fs::remove_dir_all(&root).unwrap();
+}